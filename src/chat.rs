@@ -1,68 +1,664 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::future;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use iced::widget::text_editor::{Action, Edit};
+use anyhow::{anyhow, Context};
+use iced::futures::{stream, StreamExt};
+use iced::theme::palette::Pair;
+use iced::widget::text_editor::{Action, Edit, Motion};
 use iced::widget::{
-    button, checkbox, column, container, horizontal_space, pick_list, row, scrollable, text_editor,
-    Column, Container, Scrollable,
+    button, checkbox, column, container, horizontal_rule, horizontal_space, pick_list, row,
+    scrollable, text, text_editor, text_input, Column, Container, Scrollable, TextInput,
 };
-use iced::{border, task, Color, Length, Task};
+use iced::{
+    border, clipboard, task, time, Color, Element, Length, Padding, Subscription, Task, Theme,
+};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
 
+use crate::export;
+use crate::focus;
+use crate::importers;
 use crate::openai;
-use crate::openai::{CompletionRequest, Message, Role};
-use crate::settings::SettingsView;
+use crate::openai::{
+    CompletionRequest, Delta, Endpoint, Message, Provider, Role, TokenLogprob, ToolCall,
+};
+use crate::placeholders;
+use crate::postprocess;
+use crate::postprocess::PostProcessor;
+use crate::schema;
+use crate::settings::{
+    invalid_text_input_style, GenerationParams, Preset, SerializedSettings, SettingsView,
+};
+use crate::stats::{Sample, SessionStats};
+use crate::tokens;
 
 #[derive(Debug, Clone)]
 pub enum ChatViewMsg {
-    ChangeRole { index: usize, role: Role },
-    EditText { index: usize, action: Action },
+    ChangeRole {
+        index: usize,
+        role: Role,
+    },
+    EditText {
+        index: usize,
+        action: Action,
+    },
+    ToolCallIdChanged {
+        index: usize,
+        id: String,
+    },
+    /// Adds a tool message answering the tool call `call` of the message at `index`, after the
+    /// tool messages already following it
+    AddToolResult {
+        index: usize,
+        call: usize,
+    },
     AddMessage,
-    DeleteMessage { index: usize },
+    /// Appends an empty user message after a response and focuses its editor
+    AddUserAndFocus,
+    InsertMessageAbove {
+        index: usize,
+    },
+    InsertMessageBelow {
+        index: usize,
+    },
+    DeleteMessage {
+        index: usize,
+    },
+    /// The message last edited, pressed twice to confirm like the delete button
+    DeleteFocused,
+    /// Of the message last edited, through system, user and assistant
+    CycleFocusedRole,
+    /// Sent a while after a delete started awaiting confirmation
+    CancelDelete {
+        index: usize,
+    },
+    /// Drops the messages after a user message and runs again, confirmed first if any of them
+    /// have content
+    RegenerateFrom {
+        index: usize,
+    },
     Run,
     Stop,
-    Completion { delta: Result<String, String> },
+    /// Sent once the run gets its slot in the request queue and the request is sent
+    Started,
+    /// Sent once the stream ends by itself
+    Finished,
+    Completion {
+        delta: Result<Delta, String>,
+    },
+    /// Holds back the streamed deltas until resumed, the stream itself keeps running
+    TogglePaused,
+    ToggleLogprobs,
+    ToggleHeaders,
+    ToggleReasoning {
+        index: usize,
+    },
+    ToggleCollapsed {
+        index: usize,
+    },
+    TogglePinned {
+        index: usize,
+    },
+    ToggleStats,
+    /// Saves every sample of the session stats to a CSV file
+    ExportStats,
+    StatsExported(Result<String, String>),
     StickToBottom(bool),
+    Scrolled(scrollable::Viewport),
+    WaitingTick,
+    /// Reveals the next characters of the typewriter display
+    TypewriterTick,
+    CopyCodeBlocks {
+        index: usize,
+    },
+    /// Puts the failed message back as it was before the run, then runs again
+    Retry,
+    /// Runs again with the incomplete last message sent as a prefill to continue from
+    Continue,
+    /// Puts the text cut while streaming back into the editor
+    ShowAll {
+        index: usize,
+    },
+    CopyAll {
+        index: usize,
+    },
+    /// Handled by the playground, which opens the fork in a new tab
+    Fork {
+        index: usize,
+    },
+    /// Appends an empty assistant message to write the start of the response in
+    AddAssistantPrefix,
+    ImportJson,
+    ImportedJson(Option<String>),
+    /// Copies the conversation as an OpenAI format messages array
+    CopyJson,
+    /// The request as a script using the `openai` Python SDK
+    CopyPython,
+    SavePython,
+    PythonSaved(Result<String, String>),
+    FileDropped(PathBuf),
+    /// Snapshots the conversation to a file, then starts over
+    NewConversation,
+    SnapshotSaved(Result<String, String>),
+    /// Handled by the playground, which applies the imported params to the settings
+    ApplyImportedParams,
+    /// Handled by the playground, which starts new conversations with this one from then on
+    SaveAsTemplate,
+    TestCaseNameChanged(String),
+    /// Stops the run if there's one, then saves the conversation under the test case name
+    SaveTestCase,
+    TestCaseSaved(Result<String, String>),
+    DismissImportedParams,
+    FileRead {
+        name: String,
+        contents: Result<Vec<u8>, String>,
+    },
+    DismissNotice,
+    SelectPreset(Preset),
+    ClearPreset,
+    ToggleOverrides,
+    OverrideModelChanged(String),
+    OverrideMaxTokensChanged(String),
+    OverrideTemperatureChanged(String),
+    ClearOverrides,
 }
 
 struct UiChatMsg {
     role: Role,
     content: text_editor::Content,
+    /// Streamed by reasoning models, shown above the content but never sent back
+    reasoning: String,
+    reasoning_collapsed: bool,
+    /// Only the first few lines are shown, the whole message is still sent
+    collapsed: bool,
+    /// Sent even when it's outside the context window
+    pinned: bool,
+    tool_call_id: Option<String>,
+    /// Made by an assistant message
+    tool_calls: Vec<ToolCall>,
+    /// The start of the text, cut from the editor while streaming. The editor shows
+    /// `ELIDED_MARKER` in its place, which isn't part of the text.
+    elided: Option<String>,
+    /// Its run was stopped before the model finished it
+    incomplete: bool,
+    /// Of the text, recounted as it changes
+    counted: Option<tokens::Counted>,
 }
 
 impl UiChatMsg {
-    const ROLES: &'static [Role] = &[Role::System, Role::User, Role::Assistant];
+    const ROLES: &'static [Role] = &[Role::System, Role::User, Role::Assistant, Role::Tool];
+
+    /// Lines shown of a collapsed message, longer messages can be collapsed
+    const COLLAPSED_LINES: usize = 5;
+
+    /// The first line of the editor of a message with an elided start
+    const ELIDED_MARKER: &'static str = "[…truncated…]";
+
+    fn collapsible(&self) -> bool {
+        self.collapsed || self.content.line_count() > Self::COLLAPSED_LINES
+    }
+
+    fn new(role: Role, content: text_editor::Content) -> Self {
+        Self {
+            role,
+            content,
+            reasoning: String::new(),
+            reasoning_collapsed: false,
+            collapsed: false,
+            pinned: false,
+            tool_call_id: None,
+            tool_calls: vec![],
+            elided: None,
+            incomplete: false,
+            counted: None,
+        }
+    }
+
+    fn to_message(&self) -> Message {
+        Message {
+            content: self.text(),
+            role: self.role,
+            // Only tool messages answer a call
+            tool_call_id: self
+                .tool_call_id
+                .clone()
+                .filter(|_| self.role == Role::Tool),
+            // Only assistant messages make them
+            tool_calls: match self.role {
+                Role::Assistant => self.tool_calls.clone(),
+                _ => vec![],
+            },
+        }
+    }
+
+    /// The whole text, including what was cut from the editor
+    fn text(&self) -> String {
+        match &self.elided {
+            Some(elided) => {
+                let mut text = format!("{elided}{}", self.shown_lines().join("\n"));
+
+                // Like the editor's text
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+
+                text
+            }
+            None => self.content.text(),
+        }
+    }
+
+    /// Replaces the text with what the post-processors make of it, if they change it
+    fn post_process(&mut self, processors: &[PostProcessor]) {
+        let text = self.text();
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+
+        let processed = postprocess::apply(processors, text);
+
+        if processed != text {
+            self.content = text_editor::Content::with_text(&processed);
+            self.elided = None;
+        }
+    }
+
+    /// The editor's lines without the marker
+    fn shown_lines(&self) -> Vec<String> {
+        let mut lines = self
+            .content
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+
+        if self.elided.is_some()
+            && lines
+                .first()
+                .is_some_and(|line| line == Self::ELIDED_MARKER)
+        {
+            lines.remove(0);
+        }
+
+        lines
+    }
+
+    /// Cuts all but the last `keep` lines from the editor once it has more than `cap`
+    fn cap_lines(&mut self, cap: usize, keep: usize) {
+        if cap == 0 || self.content.line_count() <= cap {
+            return;
+        }
+
+        // By lines rather than the text, which always ends with a newline
+        let mut lines = self.shown_lines();
+        let cut = lines.len().saturating_sub(keep.max(1));
+
+        let elided = self.elided.get_or_insert_with(String::new);
+
+        for line in lines.drain(..cut) {
+            elided.push_str(&line);
+            elided.push('\n');
+        }
+
+        self.content = text_editor::Content::with_text(&format!(
+            "{}\n{}",
+            Self::ELIDED_MARKER,
+            lines.join("\n")
+        ));
+    }
+
+    fn show_all(&mut self) {
+        if let Some(elided) = &self.elided {
+            self.content = text_editor::Content::with_text(&format!(
+                "{elided}{}",
+                self.shown_lines().join("\n")
+            ));
+            self.elided = None;
+        }
+    }
+
+    fn empty_user() -> Self {
+        Self::new(Role::User, text_editor::Content::new())
+    }
+
+    fn from_message(msg: Message) -> Self {
+        Self {
+            tool_call_id: msg.tool_call_id,
+            tool_calls: msg.tool_calls,
+            ..Self::new(msg.role, text_editor::Content::with_text(&msg.content))
+        }
+    }
+}
+
+/// Collapsible block with the reasoning of a message, visually set apart from its content
+fn reasoning_block(index: usize, message: &UiChatMsg) -> Column<ChatViewMsg> {
+    let toggle = button(
+        text(format!(
+            "{} Reasoning (~{} tokens)",
+            if message.reasoning_collapsed {
+                "▸"
+            } else {
+                "▾"
+            },
+            tokens::estimate(&message.reasoning)
+        ))
+        .size(12),
+    )
+    .style(button::text)
+    .on_press(ChatViewMsg::ToggleReasoning { index });
+
+    let block = (!message.reasoning_collapsed).then(|| {
+        container(
+            text(message.reasoning.as_str())
+                .size(13)
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().background.weak.text),
+                }),
+        )
+        .style(|theme: &Theme| container::Style {
+            border: border::rounded(2)
+                .width(1.0)
+                .color(theme.extended_palette().background.strong.color),
+            ..Default::default()
+        })
+        .width(Length::Fill)
+        .padding(5)
+    });
+
+    column([toggle.into()]).push_maybe(block)
+}
+
+/// Of a role's badge, from the theme so they stay readable in light and dark themes
+fn role_colors(role: Role, theme: &Theme) -> Pair {
+    let palette = theme.extended_palette();
+
+    match role {
+        Role::System => palette.secondary.base,
+        Role::User => palette.primary.base,
+        Role::Assistant => palette.success.base,
+        Role::Tool => palette.danger.weak,
+    }
+}
+
+/// Above the first message in the context window, the trimmed messages above it aren't sent
+fn trim_divider(trimmed: usize) -> Column<'static, ChatViewMsg> {
+    let label = match trimmed {
+        1 => "1 message above isn't sent, it's outside the context window".to_string(),
+        _ => format!("{trimmed} messages above aren't sent, they're outside the context window"),
+    };
+
+    column([
+        horizontal_rule(1).into(),
+        container(text(label).size(12).style(|theme: &Theme| text::Style {
+            color: Some(theme.extended_palette().background.weak.text),
+        }))
+        .center_x(Length::Fill)
+        .into(),
+    ])
+    .spacing(5)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn message_widget(
     (index, message): (usize, &UiChatMsg),
     not_inferencing: bool,
+    waiting_indicator: Option<&'static str>,
+    tokens: tokens::Count,
+    pending_delete: bool,
+    pending_regenerate: bool,
+    failed: bool,
+    continuable: bool,
+    font_size: f32,
+    role_badge: bool,
 ) -> Container<ChatViewMsg> {
+    let role = message.role;
+
     container(
         column([
             row([
+                match role_badge {
+                    true => container(
+                        container(text(role.to_string()).size(12))
+                            .padding([2, 8])
+                            .style(move |theme: &Theme| {
+                                let pair = role_colors(role, theme);
+
+                                container::Style {
+                                    text_color: Some(pair.text),
+                                    background: Some(pair.color.into()),
+                                    border: border::rounded(8),
+                                    ..Default::default()
+                                }
+                            }),
+                    )
+                    .padding(Padding {
+                        top: 6.0,
+                        bottom: 5.0,
+                        right: 0.0,
+                        left: 0.0,
+                    })
+                    .into(),
+                    false => horizontal_space().width(0).into(),
+                },
                 pick_list(UiChatMsg::ROLES, Some(message.role), move |role| {
                     ChatViewMsg::ChangeRole { index, role }
                 })
                 .into(),
+                match waiting_indicator {
+                    Some(indicator) => container(text(indicator))
+                        .padding(Padding {
+                            top: 5.0,
+                            bottom: 5.0,
+                            right: 0.0,
+                            left: 0.0,
+                        })
+                        .into(),
+                    None => horizontal_space().width(0).into(),
+                },
                 horizontal_space().into(),
-                button("Delete")
-                    .style(button::danger)
-                    .on_press_maybe(not_inferencing.then_some(ChatViewMsg::DeleteMessage { index }))
+                container(
+                    text(match message.role {
+                        Role::Assistant => {
+                            let words = tokens::word_count(&message.text());
+
+                            format!(
+                                "{words} words, ~{} min read, {tokens} tokens",
+                                tokens::reading_minutes(words)
+                            )
+                        }
+                        Role::Tool => match &message.tool_call_id {
+                            Some(id) => format!("{id}, {tokens} tokens"),
+                            None => format!("{tokens} tokens"),
+                        },
+                        _ => format!("{tokens} tokens"),
+                    })
+                    .size(12),
+                )
+                .padding(Padding {
+                    top: 7.0,
+                    bottom: 5.0,
+                    right: 0.0,
+                    left: 0.0,
+                })
+                .into(),
+                match message.role {
+                    Role::Assistant => button(text("Copy code").size(12))
+                        .style(button::secondary)
+                        .on_press(ChatViewMsg::CopyCodeBlocks { index })
+                        .into(),
+                    Role::User => button(
+                        text(if pending_regenerate {
+                            "Confirm discarding the rest?"
+                        } else {
+                            "Regenerate from here"
+                        })
+                        .size(12),
+                    )
+                    .style(if pending_regenerate {
+                        button::danger
+                    } else {
+                        button::secondary
+                    })
+                    .on_press_maybe(
+                        not_inferencing.then_some(ChatViewMsg::RegenerateFrom { index }),
+                    )
+                    .into(),
+                    _ => horizontal_space().width(0).into(),
+                },
+                match failed {
+                    true => button(text("Retry").size(12))
+                        .style(button::primary)
+                        .on_press_maybe(not_inferencing.then_some(ChatViewMsg::Retry))
+                        .into(),
+                    false => horizontal_space().width(0).into(),
+                },
+                match message.incomplete {
+                    true => {
+                        container(
+                            text("Incomplete")
+                                .size(12)
+                                .style(|theme: &Theme| text::Style {
+                                    color: Some(theme.palette().danger),
+                                }),
+                        )
+                        .padding(Padding {
+                            top: 7.0,
+                            bottom: 5.0,
+                            right: 0.0,
+                            left: 0.0,
+                        })
+                        .into()
+                    }
+                    false => horizontal_space().width(0).into(),
+                },
+                match continuable {
+                    true => button(text("Continue").size(12))
+                        .style(button::primary)
+                        .on_press_maybe(not_inferencing.then_some(ChatViewMsg::Continue))
+                        .into(),
+                    false => horizontal_space().width(0).into(),
+                },
+                match message.elided.is_some() {
+                    true => row([
+                        button(text("Show all").size(12))
+                            .style(button::secondary)
+                            .on_press_maybe(
+                                not_inferencing.then_some(ChatViewMsg::ShowAll { index }),
+                            )
+                            .into(),
+                        button(text("Copy all").size(12))
+                            .style(button::secondary)
+                            .on_press(ChatViewMsg::CopyAll { index })
+                            .into(),
+                    ])
+                    .spacing(5)
+                    .into(),
+                    false => horizontal_space().width(0).into(),
+                },
+                match message.collapsible() {
+                    true => button(
+                        text(if message.collapsed {
+                            "Show more"
+                        } else {
+                            "Collapse"
+                        })
+                        .size(12),
+                    )
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::ToggleCollapsed { index })
                     .into(),
+                    false => horizontal_space().width(0).into(),
+                },
+                button(text(if message.pinned { "Unpin" } else { "Pin" }).size(12))
+                    .style(if message.pinned {
+                        button::primary
+                    } else {
+                        button::secondary
+                    })
+                    .on_press(ChatViewMsg::TogglePinned { index })
+                    .into(),
+                button(text("Fork here").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::Fork { index })
+                    .into(),
+                button(text("+ Above").size(12))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        not_inferencing.then_some(ChatViewMsg::InsertMessageAbove { index }),
+                    )
+                    .into(),
+                button(text("+ Below").size(12))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        not_inferencing.then_some(ChatViewMsg::InsertMessageBelow { index }),
+                    )
+                    .into(),
+                button(if pending_delete {
+                    "Confirm delete?"
+                } else {
+                    "Delete"
+                })
+                .style(button::danger)
+                .on_press_maybe(not_inferencing.then_some(ChatViewMsg::DeleteMessage { index }))
+                .into(),
             ])
+            .spacing(5)
             .into(),
+            match message.role {
+                Role::Tool => {
+                    let id = message.tool_call_id.as_deref().unwrap_or_default();
+
+                    // The API rejects tool messages that don't answer a call
+                    let mut input = text_input("ID of the tool call this is the result of", id)
+                        .id(focus::skipped_id())
+                        .size(14)
+                        .style(match id.is_empty() {
+                            true => invalid_text_input_style,
+                            false => text_input::default,
+                        });
+
+                    if not_inferencing {
+                        input =
+                            input.on_input(move |id| ChatViewMsg::ToolCallIdChanged { index, id });
+                    }
+
+                    input.into()
+                }
+                _ => Column::new().into(),
+            },
+            match message.reasoning.is_empty() {
+                true => Column::new().into(),
+                false => reasoning_block(index, message).into(),
+            },
             {
-                let mut editor = text_editor(&message.content).placeholder(match message.role {
-                    Role::System => "Set a system prompt...",
-                    Role::User => "Enter your prompt...",
-                    Role::Assistant => "Enter the assistant's response...",
-                });
+                let mut editor = text_editor(&message.content)
+                    .placeholder(match message.role {
+                        Role::System => "Set a system prompt...",
+                        Role::User => "Enter your prompt...",
+                        Role::Assistant => "Enter the assistant's response...",
+                        Role::Tool => "Enter the tool's result...",
+                    })
+                    .size(font_size);
 
-                if not_inferencing {
+                // Still an editor rather than text so the editors' focus order is unchanged,
+                // the default line height is 1.3 times the font size plus 5 padding each side
+                if message.collapsed {
+                    editor = editor.height(Length::Fixed(
+                        UiChatMsg::COLLAPSED_LINES as f32 * font_size * 1.3 + 10.0,
+                    ));
+                }
+
+                // Edits could land in the marker, the whole text has to be shown to edit it
+                if not_inferencing && message.elided.is_none() {
                     editor = editor.on_action(move |action| ChatViewMsg::EditText { index, action })
                 }
 
                 editor.into()
             },
+            tool_calls(index, message, not_inferencing).into(),
         ])
         .spacing(5.0),
     )
@@ -70,172 +666,2476 @@ fn message_widget(
     .padding(5.0)
 }
 
-enum InferenceStatus {
-    Idle,
-    Inferencing {
-        #[allow(dead_code)]
-        abort_handle: task::Handle,
-    },
-}
+/// The tool calls an assistant message made, each with a button to add a message with its result
+fn tool_calls(index: usize, message: &UiChatMsg, not_inferencing: bool) -> Column<ChatViewMsg> {
+    if message.role != Role::Assistant {
+        return Column::new();
+    }
 
-pub struct ChatView {
-    messages: Vec<UiChatMsg>,
-    inference_status: InferenceStatus,
-    stick_to_bottom: bool,
+    Column::with_children(
+        message
+            .tool_calls
+            .iter()
+            .enumerate()
+            .map(|(call, tool_call)| {
+                row([
+                    container(
+                        text(format!(
+                            "Called {}({}), {}",
+                            tool_call.function.name, tool_call.function.arguments, tool_call.id
+                        ))
+                        .size(12)
+                        .font(iced::Font::MONOSPACE),
+                    )
+                    .width(Length::Fill)
+                    .padding(Padding {
+                        top: 5.0,
+                        bottom: 5.0,
+                        right: 0.0,
+                        left: 0.0,
+                    })
+                    .into(),
+                    button(text("Add result").size(12))
+                        .style(button::secondary)
+                        .on_press_maybe(
+                            not_inferencing.then_some(ChatViewMsg::AddToolResult { index, call }),
+                        )
+                        .into(),
+                ])
+                .spacing(5)
+                .into()
+            }),
+    )
+    .spacing(5)
 }
 
-impl ChatView {
-    pub fn new() -> Self {
-        Self {
-            messages: vec![UiChatMsg {
-                role: Role::User,
-                content: text_editor::Content::new(),
-            }],
-            inference_status: InferenceStatus::Idle,
-            stick_to_bottom: false,
+/// Extracts the contents of ```-fenced code blocks, an unclosed block runs to the end.
+fn fenced_code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(lines) => blocks.push(lines.join("\n")),
+                None => current = Some(vec![]),
+            }
+        } else if let Some(lines) = &mut current {
+            lines.push(line);
         }
     }
 
-    pub fn update(&mut self, settings_view: &SettingsView, msg: ChatViewMsg) -> Task<ChatViewMsg> {
-        match msg {
-            ChatViewMsg::ChangeRole { index, role } => {
-                if matches!(self.inference_status, InferenceStatus::Idle) {
-                    self.messages[index].role = role;
-                }
+    blocks.extend(current.map(|lines| lines.join("\n")));
 
-                Task::none()
-            }
-            ChatViewMsg::EditText { index, action } => {
-                self.messages[index].content.perform(action);
+    blocks
+}
 
-                Task::none()
-            }
-            ChatViewMsg::AddMessage => {
-                self.messages.push(UiChatMsg {
-                    role: Role::User,
-                    content: text_editor::Content::new(),
-                });
+/// The conversation up to and including the message at `index`
+fn fork_messages(messages: &[Message], index: usize) -> Vec<Message> {
+    messages.iter().take(index + 1).cloned().collect()
+}
 
-                Task::none()
-            }
-            ChatViewMsg::DeleteMessage { index } => {
-                self.messages.remove(index);
+/// Index of the first message sent when only the last `keep_last` are, the leading system
+/// message is sent regardless. 0 keeps every message.
+fn context_start(messages: &[Message], keep_last: usize) -> usize {
+    if keep_last == 0 {
+        return 0;
+    }
 
-                Task::none()
-            }
-            ChatViewMsg::Run => {
-                let settings = settings_view.settings();
+    let leading_system = messages.first().is_some_and(|msg| msg.role == Role::System);
 
-                let saved_settings = settings.saved();
+    messages
+        .len()
+        .saturating_sub(keep_last)
+        .max(leading_system as usize)
+}
 
-                let req = CompletionRequest::new(
-                    self.messages
-                        .iter()
-                        .map(|ui_msg| Message {
-                            content: ui_msg.content.text(),
-                            role: ui_msg.role,
-                        })
-                        .collect(),
-                    saved_settings.model.clone(),
-                    saved_settings.max_tokens.parsed().unwrap_or_default(),
-                    saved_settings.temperature.parsed().unwrap_or_default(),
-                );
-
-                let (task, abort_handle) = Task::stream(openai::completions(
-                    saved_settings.base_url.as_str(),
-                    saved_settings.api_key.as_str(),
-                    req,
-                ))
-                .map(|res| ChatViewMsg::Completion {
-                    delta: res.map_err(|err| err.to_string()),
-                })
-                .chain(Task::done(ChatViewMsg::Stop))
-                .abortable();
+/// The messages sent to the API, trimmed to the context window. The messages at `pinned` are
+/// kept wherever they are.
+fn trim_context(messages: &[Message], keep_last: usize, pinned: &[usize]) -> Vec<Message> {
+    let start = context_start(messages, keep_last);
 
-                self.inference_status = InferenceStatus::Inferencing {
-                    abort_handle: abort_handle.abort_on_drop(),
-                };
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(index, msg)| {
+            *index >= start || (*index == 0 && msg.role == Role::System) || pinned.contains(index)
+        })
+        .map(|(_, msg)| msg.clone())
+        .collect()
+}
 
-                let is_last_msg_assistant = self
-                    .messages
-                    .last()
-                    .is_some_and(|msg| msg.role == Role::Assistant);
+/// The messages with their trailing whitespace stripped
+fn trim_trailing_whitespace(messages: &[Message]) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|msg| Message {
+            content: msg.content.trim_end().to_string(),
+            ..msg.clone()
+        })
+        .collect()
+}
 
-                if !is_last_msg_assistant {
-                    self.messages.push(UiChatMsg {
-                        role: Role::Assistant,
-                        content: text_editor::Content::new(),
-                    })
-                }
+/// Drops a trailing assistant message unless it's sent as a prefill for the model to continue
+fn prefill(mut messages: Vec<Message>, assistant_prefill: bool) -> Vec<Message> {
+    if !assistant_prefill
+        && messages
+            .last()
+            .is_some_and(|msg| msg.role == Role::Assistant)
+    {
+        messages.pop();
+    }
 
-                task
-            }
-            ChatViewMsg::Stop => {
-                self.inference_status = InferenceStatus::Idle;
+    messages
+}
 
-                Task::none()
-            }
-            ChatViewMsg::Completion { delta } => {
-                if let Some(msg) = self.messages.last_mut() {
-                    msg.content
-                        .perform(Action::Edit(Edit::Paste(Arc::new(delta.unwrap_or_else(
-                            |err| {
-                                self.inference_status = InferenceStatus::Idle;
-
-                                format!("\n\nRan into an error:\n{err}")
-                            },
-                        )))));
-
-                    if self.stick_to_bottom {
-                        return scrollable::snap_to(
-                            scrollable::Id::new("messages"),
-                            scrollable::RelativeOffset::END,
-                        );
-                    }
+/// Appends an empty assistant message after a trailing user message, for backends that expect
+/// one to respond in
+fn empty_assistant_turn(mut messages: Vec<Message>) -> Vec<Message> {
+    if messages.last().is_some_and(|msg| msg.role == Role::User) {
+        messages.push(Message {
+            content: String::new(),
+            role: Role::Assistant,
+            tool_call_id: None,
+            tool_calls: vec![],
+        });
+    }
+
+    messages
+}
+
+/// Where conversations are snapshotted to when starting a new one
+const SNAPSHOTS_DIR: &str = "conversations";
+
+/// A saved conversation, along with the params it was generated with
+#[derive(Debug, Serialize)]
+struct Snapshot {
+    params: GenerationParams,
+    messages: Vec<Message>,
+    /// The run of the last message was stopped before it finished
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    last_incomplete: bool,
+}
+
+/// A conversation imported from JSON
+struct Imported {
+    messages: Vec<Message>,
+    params: Option<GenerationParams>,
+    /// Only saved conversations have one
+    last_incomplete: bool,
+    /// Of the messages that were skipped
+    warnings: Vec<String>,
+}
+
+/// Where conversations are saved as test cases, for building prompt regression suites
+const TEST_CASES_DIR: &str = "test_cases";
+
+/// A snapshot saved under a name, with the seed it was run with for reproducing it
+#[derive(Debug, Serialize)]
+struct TestCase {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+/// The name in lowercase with anything but letters and digits turned into dashes, names without
+/// either fall back to `test-case`
+fn test_case_file_name(name: &str) -> String {
+    let slug = name
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match slug.is_empty() {
+        true => "test-case.json".to_string(),
+        false => format!("{slug}.json"),
+    }
+}
+
+/// Never overwrites an existing test case of the same name
+async fn save_test_case(test_case: TestCase) -> anyhow::Result<String> {
+    let path = format!("{TEST_CASES_DIR}/{}", test_case_file_name(&test_case.name));
+
+    tokio::fs::create_dir_all(TEST_CASES_DIR).await?;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .await
+        .with_context(|| format!("Couldn't create {path}, is the name already taken?"))?;
+
+    file.write_all(serde_json::to_string_pretty(&test_case)?.as_bytes())
+        .await?;
+
+    Ok(path)
+}
+
+async fn save_snapshot(snapshot: Snapshot) -> anyhow::Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("{SNAPSHOTS_DIR}/{timestamp}.json");
+
+    tokio::fs::create_dir_all(SNAPSHOTS_DIR).await?;
+    tokio::fs::write(&path, serde_json::to_string_pretty(&snapshot)?).await?;
+
+    Ok(path)
+}
+
+/// A snapshot, a bare OpenAI format messages array or another tool's export, returns the
+/// messages, the params if there were any and the import warnings
+fn parse_conversation(json: &str) -> anyhow::Result<Imported> {
+    let value = serde_json::from_str::<Value>(json)?;
+
+    if let Some(format) = importers::Format::detect(&value) {
+        let (messages, warnings) = format
+            .import(value)
+            .with_context(|| format!("Couldn't import the {format}"))?;
+
+        return Ok(Imported {
+            messages,
+            params: None,
+            last_incomplete: false,
+            warnings,
+        });
+    }
+
+    match value {
+        Value::Array(values) => {
+            let (messages, warnings) = openai::parse_message_values(values);
+
+            Ok(Imported {
+                messages,
+                params: None,
+                last_incomplete: false,
+                warnings,
+            })
+        }
+        Value::Object(mut snapshot) => {
+            let Some(Value::Array(values)) = snapshot.remove("messages") else {
+                return Err(anyhow!("Expected a messages array"));
+            };
+
+            let (messages, mut warnings) = openai::parse_message_values(values);
+
+            let params = match snapshot.remove("params").map(serde_json::from_value) {
+                Some(Ok(params)) => Some(params),
+                Some(Err(err)) => {
+                    warnings.push(format!("Skipped the params: {err}"));
+                    None
                 }
+                None => None,
+            };
 
-                Task::none()
-            }
-            ChatViewMsg::StickToBottom(value) => {
-                self.stick_to_bottom = value;
+            let last_incomplete = snapshot
+                .get("last_incomplete")
+                .and_then(Value::as_bool)
+                .unwrap_or_default();
 
-                Task::none()
-            }
+            Ok(Imported {
+                messages,
+                params,
+                last_incomplete,
+                warnings,
+            })
         }
+        _ => Err(anyhow!("Expected a messages array or a saved conversation")),
     }
+}
 
-    fn message_list(&self, not_inferencing: bool) -> Scrollable<ChatViewMsg> {
-        scrollable(
-            column(
-                self.messages
-                    .iter()
-                    .enumerate()
-                    .map(|pair| message_widget(pair, not_inferencing))
-                    .map(Into::into)
-                    .chain(std::iter::once(
-                        container(
-                            button("+ Add Message")
-                                .on_press_maybe(not_inferencing.then_some(ChatViewMsg::AddMessage))
-                                .style(button::secondary),
-                        )
-                        .center_x(Length::Fill)
-                        .into(),
-                    )),
-            )
-            .spacing(10),
-        )
-        .id(scrollable::Id::new("messages"))
-        .spacing(3)
+/// Dropped files are cut to this many characters so huge files don't freeze the editor
+const MAX_DROPPED_CHARS: usize = 100_000;
+
+fn max_messages(settings_view: &SettingsView) -> usize {
+    settings_view
+        .settings()
+        .saved()
+        .max_messages
+        .parsed()
+        .unwrap_or(0)
+}
+
+/// Pastes at the end whatever the cursor or selection, the user may have clicked into the
+/// message being generated
+fn append(content: &mut text_editor::Content, text: String) {
+    content.perform(Action::Move(Motion::DocumentEnd));
+    content.perform(Action::Edit(Edit::Paste(Arc::new(text))));
+}
+
+/// Cuts the text to `max_chars`, returning whether it was cut
+fn cap_text(text: String, max_chars: usize) -> (String, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text, false),
     }
+}
 
-    pub fn view(&self) -> Column<ChatViewMsg> {
-        let not_inferencing = matches!(self.inference_status, InferenceStatus::Idle);
+/// Warning colored button for when the prompt approaches the token budget
+fn warning_button(theme: &Theme, status: button::Status) -> button::Style {
+    let base = button::primary(theme, status);
 
-        column([
-            container(self.message_list(not_inferencing))
-                .style(|_| container::Style {
-                    background: Some(Color::TRANSPARENT.into()),
-                    border: border::rounded(2)
+    button::Style {
+        background: Some(Color::from_rgb8(0xc2, 0x7c, 0x0e).into()),
+        ..base
+    }
+}
+
+/// Returns whether to stick to the bottom after a scroll, `None` leaves it unchanged
+fn scroll_stickiness(previous_offset: f32, offset: f32, max_offset: f32) -> Option<bool> {
+    // Small tolerance since the offset may not land exactly on the end
+    const TOLERANCE: f32 = 5.0;
+
+    if offset >= max_offset - TOLERANCE {
+        Some(true)
+    } else if offset < previous_offset {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Generation params to try out for this chat without changing the settings, blank fields fall
+/// back to the preset or the saved settings
+#[derive(Debug, Clone, Default)]
+struct Overrides {
+    model: String,
+    max_tokens: String,
+    temperature: String,
+}
+
+impl Overrides {
+    fn model(&self) -> Option<String> {
+        let model = self.model.trim();
+
+        (!model.is_empty()).then(|| model.to_string())
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.max_tokens.trim().parse().ok()
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        self.temperature.trim().parse().ok()
+    }
+
+    /// Whether every override is blank, whitespace doesn't count as one
+    fn is_empty(&self) -> bool {
+        [&self.model, &self.max_tokens, &self.temperature]
+            .iter()
+            .all(|value| value.trim().is_empty())
+    }
+}
+
+/// Styles a text input as invalid if it isn't blank and doesn't parse
+fn override_text_input<'a, T: FromStr>(
+    placeholder: &'a str,
+    value: &'a str,
+    f: impl 'a + Fn(String) -> ChatViewMsg,
+) -> TextInput<'a, ChatViewMsg> {
+    let style_fn = match value.trim().is_empty() || value.trim().parse::<T>().is_ok() {
+        true => text_input::default,
+        false => invalid_text_input_style,
+    };
+
+    text_input(placeholder, value).style(style_fn).on_input(f)
+}
+
+/// Summary of the inference state for the status bar
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Idle,
+    /// The request was sent but no token has arrived yet
+    Connecting,
+    Streaming {
+        tokens: tokens::Count,
+    },
+    /// The last run failed
+    Error(String),
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Idle => f.write_str("Idle"),
+            Status::Connecting => f.write_str("Connecting..."),
+            Status::Streaming { tokens } => write!(f, "Streaming ({tokens} tokens)"),
+            Status::Error(err) => write!(f, "Error: {err}"),
+        }
+    }
+}
+
+enum InferenceStatus {
+    Idle,
+    Inferencing {
+        #[allow(dead_code)]
+        abort_handle: task::Handle,
+        model: String,
+        started: Instant,
+        /// `None` until the first token arrives
+        time_to_first_token: Option<Duration>,
+        generated: String,
+        /// Counted as the deltas arrive
+        generated_tokens: tokens::Count,
+        finish_reason: Option<String>,
+        /// Of the generated tokens
+        counter: tokens::Counter,
+        /// The text of the message the response goes into, from before the run
+        prefix: String,
+        /// Estimated, of the messages sent
+        prompt_tokens: usize,
+        seed: Option<i64>,
+        /// The last one the stream sent
+        system_fingerprint: Option<String>,
+    },
+}
+
+impl InferenceStatus {
+    /// Shown next to the message being generated until the first token arrives
+    const WAITING_FRAMES: &'static [&'static str] = &[
+        "Waiting for a response",
+        "Waiting for a response.",
+        "Waiting for a response..",
+        "Waiting for a response...",
+    ];
+
+    fn awaiting_first_token(&self) -> bool {
+        matches!(
+            self,
+            InferenceStatus::Inferencing {
+                time_to_first_token: None,
+                ..
+            }
+        )
+    }
+}
+
+/// What the last run was generated with, to tell whether runs with the same seed are
+/// comparable
+#[derive(Debug, Clone, PartialEq)]
+struct Reproducibility {
+    seed: Option<i64>,
+    system_fingerprint: Option<String>,
+    /// An earlier run with the same seed came back with a different fingerprint, so the backend
+    /// changed and the outputs may differ despite the seed
+    fingerprint_changed: bool,
+}
+
+/// Streamed content waiting to be revealed at a steady rate
+struct Typewriter {
+    pending: String,
+    last_typed: Instant,
+    /// The stream ended, which is handled once everything is revealed
+    finished: bool,
+}
+
+impl Typewriter {
+    /// Ticks often enough for the characters to appear one at a time at typical rates
+    const TICK: Duration = Duration::from_millis(30);
+
+    /// Takes the characters due since the last ones at `rate` per second
+    fn type_out(&mut self, rate: u32) -> String {
+        let due = (rate as f64 * self.last_typed.elapsed().as_secs_f64()) as usize;
+
+        if due == 0 && rate > 0 {
+            return String::new();
+        }
+
+        let end = match rate {
+            // Turned off mid-run
+            0 => self.pending.len(),
+            _ => self
+                .pending
+                .char_indices()
+                .nth(due)
+                .map_or(self.pending.len(), |(index, _)| index),
+        };
+
+        self.last_typed = Instant::now();
+
+        self.pending.drain(..end).collect()
+    }
+}
+
+pub struct ChatView {
+    messages: Vec<UiChatMsg>,
+    inference_status: InferenceStatus,
+    stick_to_bottom: bool,
+    messages_id: scrollable::Id,
+    /// Vertical offset of the message list as of the last scroll
+    scroll_offset: f32,
+    /// Takes precedence over the saved generation params when running
+    preset: Option<Preset>,
+    waiting_frame: usize,
+    /// Shown above the bottom bar until dismissed, e.g. import warnings
+    notice: Option<String>,
+    /// Of the tokens generated by the last run, if requested
+    logprobs: Vec<TokenLogprob>,
+    show_logprobs: bool,
+    /// Of the last run's response, for debugging rate limits and routing
+    response_headers: Vec<(String, String)>,
+    show_headers: bool,
+    stats: SessionStats,
+    show_stats: bool,
+    /// The message whose editor was last used, refocused once a run stops
+    last_focused: Option<usize>,
+    /// Take precedence over both the preset and the saved generation params
+    overrides: Overrides,
+    show_overrides: bool,
+    /// A non-empty message that's deleted on the next press of its delete button
+    pending_delete: Option<usize>,
+    /// A user message whose later messages are discarded on the next press of its regenerate
+    /// button
+    pending_regenerate: Option<usize>,
+    /// Of the last run, cleared on the next
+    last_error: Option<String>,
+    /// The message an error was pasted into, with its text from before the run
+    failed_run: Option<(usize, String)>,
+    /// Of the last imported conversation, offered to be applied to the settings
+    imported_params: Option<GenerationParams>,
+    /// The oldest messages past the scrollback cap, no longer shown but still sent. Each is sent
+    /// before the message in view at its index, which moves along as messages are added and
+    /// removed before it.
+    hidden: Vec<(usize, Message)>,
+    reproducibility: Option<Reproducibility>,
+    /// The last fingerprint returned for each seed
+    fingerprints: HashMap<i64, String>,
+    /// What the typewriter display has yet to reveal, set while its rate isn't 0
+    typewriter: Option<Typewriter>,
+    /// Sends the last message as a prefill on the next run, to continue an incomplete one
+    force_prefill: bool,
+    /// While the display is paused, the deltas held back since. They're pasted (and the token
+    /// limit checked) on resuming or once the run ends.
+    paused: Option<Delta>,
+    /// What the conversation is saved as by "Save as test case"
+    test_case_name: String,
+}
+
+impl ChatView {
+    pub fn new() -> Self {
+        Self {
+            messages: vec![UiChatMsg::empty_user()],
+            inference_status: InferenceStatus::Idle,
+            stick_to_bottom: false,
+            messages_id: scrollable::Id::unique(),
+            scroll_offset: 0.0,
+            preset: None,
+            waiting_frame: 0,
+            notice: None,
+            logprobs: vec![],
+            show_logprobs: false,
+            response_headers: vec![],
+            show_headers: false,
+            stats: SessionStats::default(),
+            show_stats: false,
+            last_focused: None,
+            overrides: Overrides::default(),
+            show_overrides: false,
+            pending_delete: None,
+            pending_regenerate: None,
+            last_error: None,
+            failed_run: None,
+            imported_params: None,
+            hidden: vec![],
+            reproducibility: None,
+            fingerprints: HashMap::new(),
+            typewriter: None,
+            force_prefill: false,
+            paused: None,
+            test_case_name: String::new(),
+        }
+    }
+
+    /// A conversation of the prompt alone, after the template or default system prompt if
+    /// there's one. The prompt fills in the template's last message if it's an empty user
+    /// message, otherwise it's sent after the template.
+    pub fn from_prompt(prompt: &str, saved_settings: &SerializedSettings) -> Self {
+        let mut chat_view = Self::new();
+        chat_view.seed(saved_settings);
+
+        match chat_view.messages.last_mut() {
+            Some(msg) if msg.role == Role::User && msg.text().trim().is_empty() => {
+                msg.content = text_editor::Content::with_text(prompt);
+            }
+            _ => chat_view.messages.push(UiChatMsg::new(
+                Role::User,
+                text_editor::Content::with_text(prompt),
+            )),
+        }
+
+        chat_view
+    }
+
+    /// Still only the empty user message, nothing has been written yet
+    fn is_fresh(&self) -> bool {
+        matches!(
+            self.messages.as_slice(),
+            [msg] if msg.role == Role::User && msg.text().trim().is_empty()
+        )
+    }
+
+    /// Starts a fresh conversation with the template, or otherwise the default system prompt
+    pub fn seed(&mut self, saved_settings: &SerializedSettings) {
+        self.seed_template(&saved_settings.conversation_template);
+        self.seed_system_prompt(&saved_settings.default_system_prompt);
+    }
+
+    /// Replaces a fresh conversation with the template, unless it's empty
+    fn seed_template(&mut self, template: &[Message]) {
+        if self.is_fresh() && !template.is_empty() {
+            self.messages = template
+                .iter()
+                .cloned()
+                .map(UiChatMsg::from_message)
+                .collect();
+        }
+    }
+
+    /// Starts a fresh conversation with a system prompt, unless the prompt is blank
+    fn seed_system_prompt(&mut self, prompt: &str) {
+        if self.is_fresh() && !prompt.trim().is_empty() {
+            self.messages.insert(
+                0,
+                UiChatMsg::new(Role::System, text_editor::Content::with_text(prompt)),
+            );
+        }
+    }
+
+    fn api_messages(&self) -> Vec<Message> {
+        self.messages.iter().map(UiChatMsg::to_message).collect()
+    }
+
+    /// Every message in the order they're sent, the hidden messages in their place among those in
+    /// view. Those in view come with their index.
+    fn all_entries(&self) -> Vec<(Option<usize>, Message)> {
+        let mut hidden = self.hidden.iter().peekable();
+        let mut entries = vec![];
+
+        for (index, msg) in self.messages.iter().enumerate() {
+            while let Some((_, hidden_msg)) = hidden.next_if(|(before, _)| *before <= index) {
+                entries.push((None, hidden_msg.clone()));
+            }
+
+            entries.push((Some(index), msg.to_message()));
+        }
+
+        entries.extend(hidden.map(|(_, msg)| (None, msg.clone())));
+
+        entries
+    }
+
+    fn all_messages(&self) -> Vec<Message> {
+        self.all_entries().into_iter().map(|(_, msg)| msg).collect()
+    }
+
+    /// Hides the oldest messages past `max_messages`, a leading system message and pinned
+    /// messages stay. 0 keeps every message.
+    fn cap_scrollback(&mut self, max_messages: usize) {
+        if max_messages == 0 {
+            return;
+        }
+
+        let start = self
+            .messages
+            .first()
+            .is_some_and(|msg| msg.role == Role::System) as usize;
+        let excess = self
+            .messages
+            .len()
+            .saturating_sub(max_messages.max(start + 1));
+
+        let hiding = self
+            .messages
+            .iter()
+            .enumerate()
+            .skip(start)
+            .filter(|(_, msg)| !msg.pinned)
+            .map(|(index, _)| index)
+            .take(excess)
+            .collect::<Vec<_>>();
+
+        if hiding.is_empty() {
+            return;
+        }
+
+        // Where the message at `index` ends up, or the next one staying in view if it's hidden
+        let before = |index: usize| index - hiding.iter().filter(|hidden| **hidden < index).count();
+
+        let mut newly_hidden = vec![];
+
+        for index in hiding.iter().rev() {
+            newly_hidden.push((*index, self.messages.remove(*index).to_message()));
+        }
+
+        // Those already hidden before a message go before it
+        let mut hidden = std::mem::take(&mut self.hidden)
+            .into_iter()
+            .map(|(before, msg)| ((before, false), msg))
+            .chain(
+                newly_hidden
+                    .into_iter()
+                    .rev()
+                    .map(|(index, msg)| ((index, true), msg)),
+            )
+            .collect::<Vec<_>>();
+        hidden.sort_by_key(|(order, _)| *order);
+
+        self.hidden = hidden
+            .into_iter()
+            .map(|((index, _), msg)| (before(index), msg))
+            .collect();
+
+        self.remap_indices(|index| (!hiding.contains(&index)).then(|| before(index)));
+    }
+
+    /// Moves the indices kept into the messages to where `map` moves their messages, dropping
+    /// those whose messages are gone. A pending delete or regenerate is cancelled instead.
+    fn remap_indices(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.last_focused = self.last_focused.and_then(&map);
+        self.failed_run = self
+            .failed_run
+            .take()
+            .and_then(|(index, prefix)| map(index).map(|index| (index, prefix)));
+        self.pending_delete = None;
+        self.pending_regenerate = None;
+    }
+
+    /// Keeps the hidden messages and the indices into the messages in place around a message
+    /// inserted at `index`
+    fn inserted_at(&mut self, index: usize) {
+        for (before, _) in &mut self.hidden {
+            if *before > index {
+                *before += 1;
+            }
+        }
+
+        self.remap_indices(|shifted| Some(shifted + (shifted >= index) as usize));
+    }
+
+    /// Keeps the hidden messages and the indices into the messages in place around the message
+    /// removed from `index`
+    fn removed_at(&mut self, index: usize) {
+        for (before, _) in &mut self.hidden {
+            if *before > index {
+                *before -= 1;
+            }
+        }
+
+        self.remap_indices(|shifted| match shifted.cmp(&index) {
+            Ordering::Less => Some(shifted),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(shifted - 1),
+        });
+    }
+
+    fn python_snippet(&self, settings_view: &SettingsView) -> String {
+        let settings = settings_view.settings();
+        let saved_settings = settings.saved();
+
+        export::python_snippet(
+            &saved_settings.base_url,
+            saved_settings.endpoint,
+            self.completion_request(saved_settings),
+        )
+    }
+
+    /// Indices among all the messages of the pinned ones
+    fn pinned(&self) -> Vec<usize> {
+        self.all_entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, (shown, _))| shown.is_some_and(|shown| self.messages[shown].pinned))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// The messages before trimming to the context window
+    fn prefilled_messages(&self, saved_settings: &SerializedSettings) -> Vec<Message> {
+        // The whole conversation is the prompt for the completions endpoint
+        prefill(
+            self.all_messages(),
+            !saved_settings.drop_trailing_assistant
+                || self.force_prefill
+                || saved_settings.endpoint == Endpoint::Completions,
+        )
+    }
+
+    /// Where in view the context window starts and how many messages before it aren't sent, if
+    /// any aren't. The leading system message and pinned messages are sent anyway.
+    fn trim_boundary(&self, saved_settings: &SerializedSettings) -> Option<(usize, usize)> {
+        let entries = self.all_entries();
+        let messages = self.prefilled_messages(saved_settings);
+        let start = context_start(
+            &messages,
+            saved_settings
+                .context_window_messages
+                .parsed()
+                .unwrap_or_default(),
+        );
+
+        let trimmed = entries[..start]
+            .iter()
+            .enumerate()
+            .filter(|(index, (shown, msg))| {
+                !(*index == 0 && msg.role == Role::System)
+                    && !shown.is_some_and(|shown| self.messages[shown].pinned)
+            })
+            .count();
+
+        // A start among the hidden messages is shown before the first message in view after it
+        let shown_start = entries[start..]
+            .iter()
+            .find_map(|(shown, _)| *shown)
+            .unwrap_or(self.messages.len());
+
+        (trimmed > 0).then_some((shown_start, trimmed))
+    }
+
+    pub fn completion_request(&self, saved_settings: &SerializedSettings) -> CompletionRequest {
+        let messages = trim_context(
+            &self.prefilled_messages(saved_settings),
+            saved_settings
+                .context_window_messages
+                .parsed()
+                .unwrap_or_default(),
+            &self.pinned(),
+        );
+
+        let messages = match saved_settings.trim_trailing_whitespace {
+            true => trim_trailing_whitespace(&messages),
+            false => messages,
+        };
+
+        let messages = match saved_settings.fill_placeholders {
+            true => messages
+                .into_iter()
+                .map(|msg| Message {
+                    content: placeholders::fill(&msg.content, SystemTime::now(), |var| {
+                        std::env::var(var).ok()
+                    }),
+                    ..msg
+                })
+                .collect(),
+            false => messages,
+        };
+
+        // Only in the request, the completions endpoint has no turns to add
+        let messages = match saved_settings.empty_assistant_turn
+            && saved_settings.endpoint == Endpoint::ChatCompletions
+        {
+            true => empty_assistant_turn(messages),
+            false => messages,
+        };
+
+        let params = self.generation_params(saved_settings);
+
+        let req = CompletionRequest::new(
+            messages,
+            params.model,
+            params.max_tokens,
+            params.max_tokens_field,
+            params.temperature,
+        )
+        .with_user(saved_settings.user.trim().to_string())
+        .with_seed(saved_settings.seed())
+        .with_stop(saved_settings.stop_sequences(), saved_settings.stop_format);
+
+        let req = match saved_settings.logprobs {
+            true => req.with_logprobs(saved_settings.top_logprobs.parsed().unwrap_or_default()),
+            false => req,
+        };
+
+        match schema::parse(&saved_settings.json_schema) {
+            Ok(Some(schema)) => req.with_json_schema(schema),
+            _ => req,
+        }
+    }
+
+    /// The conversation as it's saved, with the params it's run with
+    fn snapshot(&self, saved_settings: &SerializedSettings) -> Snapshot {
+        Snapshot {
+            params: self.generation_params(saved_settings),
+            messages: match saved_settings.save_hidden_messages {
+                true => self.all_messages(),
+                false => self.api_messages(),
+            },
+            last_incomplete: self.messages.last().is_some_and(|msg| msg.incomplete),
+        }
+    }
+
+    /// The params runs use, with the same precedence as the model
+    fn generation_params(&self, saved_settings: &SerializedSettings) -> GenerationParams {
+        let (max_tokens, temperature) = match &self.preset {
+            Some(preset) => (preset.max_tokens, preset.temperature),
+            None => (
+                saved_settings.max_tokens.parsed().unwrap_or_default(),
+                saved_settings.temperature.parsed().unwrap_or_default(),
+            ),
+        };
+
+        GenerationParams {
+            model: self.model(saved_settings),
+            max_tokens: self.overrides.max_tokens().unwrap_or(max_tokens),
+            max_tokens_field: saved_settings.max_tokens_field,
+            temperature: self.overrides.temperature().unwrap_or(temperature),
+            endpoint: saved_settings.endpoint,
+        }
+    }
+
+    /// For the tokenizer model, or the model being run if it's blank
+    fn token_counter(&self, saved_settings: &SerializedSettings) -> tokens::Counter {
+        match saved_settings.tokenizer_model.trim() {
+            "" => tokens::Counter::for_model(&self.model(saved_settings)),
+            model => tokens::Counter::for_model(model),
+        }
+    }
+
+    /// Of the last imported conversation, once they're applied they're no longer offered
+    pub fn take_imported_params(&mut self) -> Option<GenerationParams> {
+        self.imported_params.take()
+    }
+
+    /// The model runs use, the override takes precedence over the preset and then the settings.
+    /// Aliases are expanded to the model they stand for.
+    pub fn model(&self, saved_settings: &SerializedSettings) -> String {
+        saved_settings.expand_model_alias(self.overrides.model().unwrap_or_else(|| {
+            match &self.preset {
+                Some(preset) => preset.model.clone(),
+                None => saved_settings.model.clone(),
+            }
+        }))
+    }
+
+    /// Inserts an empty user message at `index` and focuses its editor, unless inferencing
+    fn insert_and_focus(&mut self, index: usize) -> Task<ChatViewMsg> {
+        if !matches!(self.inference_status, InferenceStatus::Idle) {
+            return Task::none();
+        }
+
+        self.messages.insert(index, UiChatMsg::empty_user());
+        self.inserted_at(index);
+        self.last_focused = Some(index);
+
+        focus::nth(index)
+    }
+
+    /// The conversation to start new ones with
+    pub fn template(&self) -> Vec<Message> {
+        self.api_messages()
+    }
+
+    /// A new conversation with the messages up to and including `index`
+    pub fn fork(&self, index: usize) -> Self {
+        Self {
+            messages: fork_messages(&self.api_messages(), index)
+                .into_iter()
+                .map(UiChatMsg::from_message)
+                .collect(),
+            preset: self.preset.clone(),
+            overrides: self.overrides.clone(),
+            ..Self::new()
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match &self.inference_status {
+            InferenceStatus::Idle => match &self.last_error {
+                Some(err) => Status::Error(err.clone()),
+                None => Status::Idle,
+            },
+            InferenceStatus::Inferencing {
+                time_to_first_token: None,
+                ..
+            } => Status::Connecting,
+            InferenceStatus::Inferencing {
+                generated_tokens, ..
+            } => Status::Streaming {
+                tokens: *generated_tokens,
+            },
+        }
+    }
+
+    pub fn is_inferencing(&self) -> bool {
+        !matches!(self.inference_status, InferenceStatus::Idle)
+    }
+
+    /// Aborts the running inference if there is one, dropping the handle aborts the stream
+    pub fn stop_inference(&mut self) {
+        self.inference_status = InferenceStatus::Idle;
+    }
+
+    /// Records the timings of the run if it received any tokens, then stops it. Responses
+    /// `cut_short` before they got to a finish reason are marked incomplete, not every backend
+    /// sends one when the stream ends.
+    fn finish_inference(&mut self, cut_short: bool) {
+        if let InferenceStatus::Inferencing {
+            generated,
+            finish_reason: None,
+            ..
+        } = &self.inference_status
+        {
+            if let (true, false, Some(msg)) =
+                (cut_short, generated.is_empty(), self.messages.last_mut())
+            {
+                msg.incomplete = true;
+            }
+        }
+
+        if let InferenceStatus::Inferencing {
+            seed,
+            system_fingerprint,
+            ..
+        } = &self.inference_status
+        {
+            let (seed, system_fingerprint) = (*seed, system_fingerprint.clone());
+
+            self.reproducibility = Some(self.check_fingerprint(seed, system_fingerprint));
+        }
+
+        if let InferenceStatus::Inferencing {
+            model,
+            started,
+            time_to_first_token: Some(time_to_first_token),
+            generated_tokens,
+            prompt_tokens,
+            ..
+        } = &self.inference_status
+        {
+            self.stats.record(Sample {
+                model: model.clone(),
+                time_to_first_token: *time_to_first_token,
+                duration: started.elapsed(),
+                tokens: generated_tokens.tokens,
+                prompt_tokens: *prompt_tokens,
+                finished_at: SystemTime::now(),
+            });
+        }
+
+        self.stop_inference();
+    }
+
+    /// Ends the run once its stream ended, or early if `cut_short`, refocusing the message that
+    /// was being edited
+    fn end_run(&mut self, settings_view: &SettingsView, cut_short: bool) -> Task<ChatViewMsg> {
+        let was_inferencing = self.is_inferencing();
+
+        // Whatever wasn't revealed yet is pasted at once
+        let pasted = match self.typewriter.take() {
+            Some(typewriter) => self.paste(settings_view, typewriter.pending),
+            None => Task::none(),
+        };
+
+        self.finish_inference(cut_short);
+        self.cap_scrollback(max_messages(settings_view));
+
+        let refocus = self
+            .last_focused
+            .filter(|index| was_inferencing && *index < self.messages.len());
+
+        match refocus {
+            Some(index) => Task::batch([pasted, focus::nth(index)]),
+            None => pasted,
+        }
+    }
+
+    /// Reasoning counts as the first token, the content may only start long after it
+    fn received_reasoning(&mut self) {
+        if let InferenceStatus::Inferencing {
+            started,
+            time_to_first_token,
+            ..
+        } = &mut self.inference_status
+        {
+            time_to_first_token.get_or_insert_with(|| started.elapsed());
+        }
+    }
+
+    /// Appends to the message being generated, keeping it in view if sticking to the bottom
+    fn paste(&mut self, settings_view: &SettingsView, delta: String) -> Task<ChatViewMsg> {
+        let line_cap = settings_view
+            .settings()
+            .saved()
+            .stream_line_cap
+            .parsed()
+            .unwrap_or(0);
+
+        let Some(msg) = self.messages.last_mut() else {
+            return Task::none();
+        };
+
+        append(&mut msg.content, delta);
+        // Half is kept so the editor isn't rebuilt on every line past the cap
+        msg.cap_lines(line_cap, line_cap / 2);
+
+        match self.stick_to_bottom {
+            true => scrollable::snap_to(self.messages_id.clone(), scrollable::RelativeOffset::END),
+            false => Task::none(),
+        }
+    }
+
+    /// Pastes the deltas held back while paused, then pastes them live again
+    fn resume_display(&mut self, settings_view: &SettingsView) -> Task<ChatViewMsg> {
+        match self.paused.take() {
+            Some(held) if held != Delta::default() => {
+                self.update(settings_view, ChatViewMsg::Completion { delta: Ok(held) })
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// Remembers the fingerprint the seed was served with, comparing it to the last one
+    fn check_fingerprint(
+        &mut self,
+        seed: Option<i64>,
+        system_fingerprint: Option<String>,
+    ) -> Reproducibility {
+        let fingerprint_changed = match (seed, &system_fingerprint) {
+            (Some(seed), Some(fingerprint)) => self
+                .fingerprints
+                .insert(seed, fingerprint.clone())
+                .is_some_and(|last| last != *fingerprint),
+            _ => false,
+        };
+
+        Reproducibility {
+            seed,
+            system_fingerprint,
+            fingerprint_changed,
+        }
+    }
+
+    /// Marks the first token as received, trimming the response's leading whitespace first if
+    /// enabled. Whitespace only deltas don't count as the first token while trimming.
+    fn received_delta(&mut self, delta: String, trim_leading_whitespace: bool) -> String {
+        let InferenceStatus::Inferencing {
+            started,
+            time_to_first_token,
+            generated,
+            generated_tokens,
+            counter,
+            ..
+        } = &mut self.inference_status
+        else {
+            return delta;
+        };
+
+        let delta = match trim_leading_whitespace && generated.is_empty() {
+            true => delta.trim_start().to_string(),
+            false => delta,
+        };
+
+        if !delta.is_empty() {
+            time_to_first_token.get_or_insert_with(|| started.elapsed());
+            generated.push_str(&delta);
+            *generated_tokens = counter.count(generated);
+        }
+
+        delta
+    }
+
+    /// Whether the response reached the client side token limit, 0 being no limit
+    fn reached_token_limit(&self, limit: usize) -> bool {
+        match &self.inference_status {
+            InferenceStatus::Inferencing {
+                generated_tokens, ..
+            } => limit != 0 && generated_tokens.tokens >= limit,
+            InferenceStatus::Idle => false,
+        }
+    }
+
+    pub fn update(&mut self, settings_view: &SettingsView, msg: ChatViewMsg) -> Task<ChatViewMsg> {
+        let task = self.handle(settings_view, msg);
+
+        // Rather than on every frame, only the messages whose text changed are counted again
+        let counter = self.token_counter(settings_view.settings().saved());
+
+        for msg in &mut self.messages {
+            msg.counted = Some(counter.recount(&msg.text(), msg.counted));
+        }
+
+        task
+    }
+
+    /// The token count of the message at `index` as of the last update, or counted now if the
+    /// tokenizer changed since
+    fn token_count(&self, index: usize, counter: &tokens::Counter) -> tokens::Count {
+        let msg = &self.messages[index];
+
+        counter
+            .counted(msg.counted)
+            .unwrap_or_else(|| counter.count(&msg.text()))
+    }
+
+    fn handle(&mut self, settings_view: &SettingsView, msg: ChatViewMsg) -> Task<ChatViewMsg> {
+        // Anything else pressed cancels a pending delete, the streamed messages aren't presses
+        if !matches!(
+            msg,
+            ChatViewMsg::DeleteMessage { .. }
+                | ChatViewMsg::DeleteFocused
+                | ChatViewMsg::CancelDelete { .. }
+                | ChatViewMsg::Started
+                | ChatViewMsg::Completion { .. }
+                | ChatViewMsg::Scrolled(_)
+                | ChatViewMsg::WaitingTick
+                | ChatViewMsg::TypewriterTick
+        ) {
+            self.pending_delete = None;
+        }
+
+        if !matches!(
+            msg,
+            ChatViewMsg::RegenerateFrom { .. }
+                | ChatViewMsg::Started
+                | ChatViewMsg::Completion { .. }
+                | ChatViewMsg::Scrolled(_)
+                | ChatViewMsg::WaitingTick
+                | ChatViewMsg::TypewriterTick
+        ) {
+            self.pending_regenerate = None;
+        }
+
+        match msg {
+            // The held deltas go before whatever ends the run
+            msg @ (ChatViewMsg::Finished
+            | ChatViewMsg::Stop
+            | ChatViewMsg::Completion { delta: Err(_) })
+                if self.paused.is_some() =>
+            {
+                let resumed = self.resume_display(settings_view);
+
+                Task::batch([resumed, self.update(settings_view, msg)])
+            }
+            ChatViewMsg::Completion { delta: Ok(delta) } if self.paused.is_some() => {
+                // Still timed as it arrives, only the pasting waits
+                if !delta.content.trim().is_empty() || !delta.reasoning.is_empty() {
+                    self.received_reasoning();
+                }
+
+                if let Some(held) = &mut self.paused {
+                    held.merge(delta);
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::Finished
+                if self
+                    .typewriter
+                    .as_ref()
+                    .is_some_and(|typewriter| !typewriter.pending.is_empty()) =>
+            {
+                if let Some(typewriter) = &mut self.typewriter {
+                    typewriter.finished = true;
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::TogglePaused => match self.paused {
+                Some(_) => self.resume_display(settings_view),
+                None => {
+                    if self.is_inferencing() {
+                        self.paused = Some(Delta::default());
+                    }
+
+                    Task::none()
+                }
+            },
+            ChatViewMsg::ChangeRole { index, role } => {
+                if matches!(self.inference_status, InferenceStatus::Idle) {
+                    let msg = &mut self.messages[index];
+                    msg.role = role;
+
+                    if role != Role::Tool {
+                        msg.tool_call_id = None;
+                    }
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::AddToolResult { index, call } => {
+                let Some(id) = self.messages[index]
+                    .tool_calls
+                    .get(call)
+                    .map(|call| call.id.clone())
+                    .filter(|_| matches!(self.inference_status, InferenceStatus::Idle))
+                else {
+                    return Task::none();
+                };
+
+                // After the results of the earlier calls
+                let at = self.messages[index + 1..]
+                    .iter()
+                    .position(|msg| msg.role != Role::Tool)
+                    .map_or(self.messages.len(), |offset| index + 1 + offset);
+
+                let task = self.insert_and_focus(at);
+
+                let msg = &mut self.messages[at];
+                msg.role = Role::Tool;
+                msg.tool_call_id = (!id.is_empty()).then_some(id);
+
+                task
+            }
+            ChatViewMsg::ToolCallIdChanged { index, id } => {
+                if matches!(self.inference_status, InferenceStatus::Idle) {
+                    self.messages[index].tool_call_id =
+                        (!id.trim().is_empty()).then(|| id.trim().to_string());
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::EditText { index, action } => {
+                self.messages[index].content.perform(action);
+                self.last_focused = Some(index);
+
+                Task::none()
+            }
+            ChatViewMsg::AddMessage => {
+                self.messages.push(UiChatMsg::empty_user());
+
+                // Editors are locked while inferencing, so the new one is only focused after
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                self.cap_scrollback(max_messages(settings_view));
+                self.last_focused = Some(self.messages.len() - 1);
+
+                focus::nth(self.messages.len() - 1)
+            }
+            ChatViewMsg::AddUserAndFocus => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                self.messages.push(UiChatMsg::empty_user());
+                self.cap_scrollback(max_messages(settings_view));
+                self.last_focused = Some(self.messages.len() - 1);
+
+                focus::nth(self.messages.len() - 1)
+            }
+            ChatViewMsg::InsertMessageAbove { index } => self.insert_and_focus(index),
+            ChatViewMsg::InsertMessageBelow { index } => self.insert_and_focus(index + 1),
+            ChatViewMsg::DeleteMessage { index } => {
+                let confirmed = self.pending_delete.take() == Some(index);
+
+                if !confirmed && !self.messages[index].text().trim().is_empty() {
+                    self.pending_delete = Some(index);
+
+                    return Task::none();
+                }
+
+                self.messages.remove(index);
+                self.removed_at(index);
+
+                Task::none()
+            }
+            ChatViewMsg::DeleteFocused => {
+                let index = self
+                    .last_focused
+                    .filter(|index| !self.is_inferencing() && *index < self.messages.len());
+
+                match index {
+                    Some(index) => self.update(settings_view, ChatViewMsg::DeleteMessage { index }),
+                    None => Task::none(),
+                }
+            }
+            ChatViewMsg::CycleFocusedRole => {
+                let index = self
+                    .last_focused
+                    .filter(|index| !self.is_inferencing() && *index < self.messages.len());
+
+                match index {
+                    Some(index) => {
+                        let role = match self.messages[index].role {
+                            Role::System => Role::User,
+                            Role::User => Role::Assistant,
+                            Role::Assistant | Role::Tool => Role::System,
+                        };
+
+                        self.update(settings_view, ChatViewMsg::ChangeRole { index, role })
+                    }
+                    None => Task::none(),
+                }
+            }
+            ChatViewMsg::CancelDelete { index } => {
+                if self.pending_delete == Some(index) {
+                    self.pending_delete = None;
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::RegenerateFrom { index } => {
+                if self.is_inferencing() || index >= self.messages.len() {
+                    return Task::none();
+                }
+
+                let confirmed = self.pending_regenerate.take() == Some(index);
+                let discards_content = self.messages[index + 1..]
+                    .iter()
+                    .any(|msg| !msg.content.text().trim().is_empty());
+
+                if !confirmed && discards_content {
+                    self.pending_regenerate = Some(index);
+
+                    return Task::none();
+                }
+
+                self.messages.truncate(index + 1);
+                self.hidden.retain(|(before, _)| *before <= index);
+                self.remap_indices(|shifted| (shifted <= index).then_some(shifted));
+                self.last_focused = None;
+
+                self.update(settings_view, ChatViewMsg::Run)
+            }
+            ChatViewMsg::Run => {
+                let settings = settings_view.settings();
+
+                let saved_settings = settings.saved();
+
+                let req = self.completion_request(saved_settings);
+
+                let model = req.model().to_string();
+                let seed = req.seed();
+                let counter = self.token_counter(saved_settings);
+                let prompt_tokens: usize = req
+                    .messages()
+                    .iter()
+                    .map(|msg| counter.count(&msg.content).tokens)
+                    .sum();
+
+                let deltas = match (saved_settings.provider, settings.client().get()) {
+                    (Provider::Mock, _) => {
+                        openai::mock_completions(req, Duration::from_millis(50)).boxed()
+                    }
+                    (Provider::OpenAiCompatible, Ok(client)) => match saved_settings.endpoint {
+                        Endpoint::ChatCompletions => openai::completions(
+                            &client,
+                            saved_settings.base_url.as_str(),
+                            saved_settings.api_key.as_str(),
+                            req,
+                            &saved_settings.routing(),
+                            saved_settings.connect_retries(),
+                            saved_settings.delta_pointer.clone(),
+                            openai::RawLog::new(&saved_settings.raw_log_path),
+                        )
+                        .boxed(),
+                        Endpoint::Completions => openai::text_completions(
+                            &client,
+                            saved_settings.base_url.as_str(),
+                            saved_settings.api_key.as_str(),
+                            req.into_text_completion(),
+                            &saved_settings.routing(),
+                            saved_settings.connect_retries(),
+                            openai::RawLog::new(&saved_settings.raw_log_path),
+                        )
+                        .boxed(),
+                    },
+                    (Provider::OpenAiCompatible, Err(err)) => {
+                        stream::once(future::ready(Err(err))).boxed()
+                    }
+                };
+
+                let deltas = match saved_settings.delta_batch_interval.parsed() {
+                    Some(0) | None => deltas,
+                    Some(ms) => openai::batch_deltas(deltas, Duration::from_millis(ms)).boxed(),
+                };
+
+                // The run is only timed from when it's sent, not while it waits in the queue
+                let msgs =
+                    stream::once(future::ready(ChatViewMsg::Started)).chain(deltas.map(|res| {
+                        ChatViewMsg::Completion {
+                            delta: res.map_err(|err| err.to_string()),
+                        }
+                    }));
+
+                // Queued behind the runs of every tab, the mock has no rate limits
+                let msgs = match saved_settings.provider {
+                    Provider::Mock => msgs.boxed(),
+                    Provider::OpenAiCompatible => settings
+                        .queue()
+                        .throttle(saved_settings.queue_limits(), msgs)
+                        .boxed(),
+                };
+
+                let task = Task::stream(msgs);
+
+                let (task, abort_handle) =
+                    task.chain(Task::done(ChatViewMsg::Finished)).abortable();
+
+                // The response continues the last assistant message if it was sent as a prefill,
+                // otherwise an empty one left over from a previous run is reused
+                let continues_last_msg = self.messages.last().is_some_and(|msg| {
+                    msg.role == Role::Assistant
+                        && (!saved_settings.drop_trailing_assistant
+                            || self.force_prefill
+                            || msg.text().trim().is_empty())
+                });
+                self.force_prefill = false;
+
+                // Without auto adding, the response goes into the last message whatever its role.
+                // Completions continue the prompt, so they always go into the last message.
+                let auto_add = (saved_settings.auto_add_assistant_message
+                    && saved_settings.endpoint == Endpoint::ChatCompletions)
+                    || self.messages.is_empty();
+
+                match continues_last_msg || !auto_add {
+                    true => {
+                        if let Some(msg) = self.messages.last_mut() {
+                            msg.reasoning.clear();
+                            msg.incomplete = false;
+                        }
+                    }
+                    false => self
+                        .messages
+                        .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new())),
+                }
+
+                self.inference_status = InferenceStatus::Inferencing {
+                    abort_handle: abort_handle.abort_on_drop(),
+                    model,
+                    started: Instant::now(),
+                    time_to_first_token: None,
+                    generated: String::new(),
+                    generated_tokens: tokens::Count::default(),
+                    finish_reason: None,
+                    counter,
+                    prefix: self
+                        .messages
+                        .last()
+                        .map(UiChatMsg::text)
+                        .unwrap_or_default(),
+                    prompt_tokens,
+                    seed,
+                    system_fingerprint: None,
+                };
+                self.waiting_frame = 0;
+                self.logprobs.clear();
+                self.response_headers.clear();
+                self.last_error = None;
+                self.failed_run = None;
+                self.paused = None;
+                self.typewriter = None;
+
+                // Keystrokes would otherwise go to an editor that's locked while inferencing
+                Task::batch([focus::unfocus(), task])
+            }
+            ChatViewMsg::Finished => {
+                if let InferenceStatus::Inferencing {
+                    generated,
+                    finish_reason,
+                    ..
+                } = &self.inference_status
+                {
+                    // Tool calls and reasoning are a response too
+                    let answered = self
+                        .messages
+                        .last()
+                        .is_some_and(|msg| !msg.tool_calls.is_empty() || !msg.reasoning.is_empty());
+
+                    // e.g. filtered responses stream nothing before ending. Noticed rather than
+                    // written into the message, which would be sent back.
+                    if generated.is_empty() {
+                        if !answered {
+                            self.notice = Some(match finish_reason {
+                                Some(reason) => {
+                                    format!("No content returned, finish_reason: {reason}")
+                                }
+                                None => "No content returned".to_string(),
+                            });
+                        }
+                    } else {
+                        let saved_settings = settings_view.settings().saved();
+
+                        if let (true, Ok(Some(schema))) = (
+                            saved_settings.validate_json_schema,
+                            schema::parse(&saved_settings.json_schema),
+                        ) {
+                            self.notice = schema::validate(&schema, generated).err();
+                        }
+
+                        if let Some(msg) = self
+                            .messages
+                            .last_mut()
+                            .filter(|msg| msg.role == Role::Assistant)
+                        {
+                            msg.post_process(&saved_settings.post_processors);
+                        }
+                    }
+                }
+
+                self.end_run(settings_view, false)
+            }
+            ChatViewMsg::Stop => self.end_run(settings_view, true),
+            ChatViewMsg::Started => {
+                if let InferenceStatus::Inferencing { started, .. } = &mut self.inference_status {
+                    *started = Instant::now();
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::Completion { delta } => {
+                let delta = match delta {
+                    Ok(delta) => {
+                        self.logprobs.extend(delta.logprobs);
+
+                        if !delta.headers.is_empty() {
+                            self.response_headers = delta.headers;
+                        }
+
+                        if let InferenceStatus::Inferencing {
+                            finish_reason,
+                            system_fingerprint,
+                            ..
+                        } = &mut self.inference_status
+                        {
+                            if delta.finish_reason.is_some() {
+                                *finish_reason = delta.finish_reason;
+                            }
+
+                            if delta.system_fingerprint.is_some() {
+                                *system_fingerprint = delta.system_fingerprint;
+                            }
+                        }
+
+                        if !delta.tool_calls.is_empty() {
+                            if let Some(msg) = self.messages.last_mut() {
+                                openai::append_tool_calls(&mut msg.tool_calls, delta.tool_calls);
+                            }
+                        }
+
+                        if !delta.reasoning.is_empty() {
+                            self.received_reasoning();
+
+                            if let Some(msg) = self.messages.last_mut() {
+                                msg.reasoning.push_str(&delta.reasoning);
+                            }
+                        }
+
+                        self.received_delta(
+                            delta.content,
+                            settings_view.settings().saved().trim_leading_whitespace,
+                        )
+                    }
+                    Err(err) => {
+                        let prefix = match &self.inference_status {
+                            InferenceStatus::Inferencing { prefix, .. } => Some(prefix.clone()),
+                            InferenceStatus::Idle => None,
+                        };
+
+                        // The stream broke off, what was generated so far is marked incomplete
+                        let ended = self.end_run(settings_view, true);
+
+                        self.failed_run = prefix
+                            .and_then(|prefix| Some((self.messages.len().checked_sub(1)?, prefix)));
+
+                        let pasted =
+                            self.paste(settings_view, format!("\n\nRan into an error:\n{err}"));
+                        self.last_error = Some(err);
+
+                        return Task::batch([ended, pasted]);
+                    }
+                };
+
+                let rate = settings_view
+                    .settings()
+                    .saved()
+                    .typewriter_rate
+                    .parsed()
+                    .unwrap_or(0);
+
+                let inferencing = self.is_inferencing();
+
+                // Revealed by the ticks instead, unless the run already ended
+                let task = match (&mut self.typewriter, inferencing) {
+                    (Some(typewriter), true) => {
+                        typewriter.pending.push_str(&delta);
+
+                        Task::none()
+                    }
+                    (None, true) if rate > 0 => {
+                        self.typewriter = Some(Typewriter {
+                            pending: delta,
+                            last_typed: Instant::now(),
+                            finished: false,
+                        });
+
+                        Task::none()
+                    }
+                    _ => {
+                        let pending = self
+                            .typewriter
+                            .take()
+                            .map(|typewriter| typewriter.pending)
+                            .unwrap_or_default();
+
+                        self.paste(settings_view, pending + &delta)
+                    }
+                };
+
+                let limit = settings_view
+                    .settings()
+                    .saved()
+                    .stop_after_tokens
+                    .parsed()
+                    .unwrap_or(0);
+
+                // Dropping the abort handle on stop ends the stream
+                match self.reached_token_limit(limit) {
+                    true => Task::batch([task, self.update(settings_view, ChatViewMsg::Stop)]),
+                    false => task,
+                }
+            }
+            ChatViewMsg::ToggleLogprobs => {
+                self.show_logprobs = !self.show_logprobs;
+
+                Task::none()
+            }
+            ChatViewMsg::ToggleStats => {
+                self.show_stats = !self.show_stats;
+
+                Task::none()
+            }
+            ChatViewMsg::ToggleHeaders => {
+                self.show_headers = !self.show_headers;
+
+                Task::none()
+            }
+            ChatViewMsg::ToggleReasoning { index } => {
+                let msg = &mut self.messages[index];
+                msg.reasoning_collapsed = !msg.reasoning_collapsed;
+
+                Task::none()
+            }
+            ChatViewMsg::TogglePinned { index } => {
+                let msg = &mut self.messages[index];
+                msg.pinned = !msg.pinned;
+
+                Task::none()
+            }
+            ChatViewMsg::ToggleCollapsed { index } => {
+                let msg = &mut self.messages[index];
+                msg.collapsed = !msg.collapsed;
+
+                Task::none()
+            }
+            ChatViewMsg::StickToBottom(value) => {
+                self.stick_to_bottom = value;
+
+                Task::none()
+            }
+            ChatViewMsg::Scrolled(viewport) => {
+                let offset = viewport.absolute_offset().y;
+                let max_offset = viewport.content_bounds().height - viewport.bounds().height;
+
+                if settings_view.settings().saved().auto_stick_to_bottom {
+                    if let Some(stick) = scroll_stickiness(self.scroll_offset, offset, max_offset) {
+                        self.stick_to_bottom = stick;
+                    }
+                }
+
+                self.scroll_offset = offset;
+
+                Task::none()
+            }
+            ChatViewMsg::WaitingTick => {
+                self.waiting_frame = self.waiting_frame.wrapping_add(1);
+
+                Task::none()
+            }
+            ChatViewMsg::TypewriterTick => {
+                let rate = settings_view
+                    .settings()
+                    .saved()
+                    .typewriter_rate
+                    .parsed()
+                    .unwrap_or(0);
+
+                let Some(typewriter) = &mut self.typewriter else {
+                    return Task::none();
+                };
+
+                let typed = typewriter.type_out(rate);
+                let done = typewriter.pending.is_empty() && typewriter.finished;
+
+                let task = match typed.is_empty() {
+                    true => Task::none(),
+                    false => self.paste(settings_view, typed),
+                };
+
+                match done {
+                    true => {
+                        self.typewriter = None;
+
+                        Task::batch([task, self.update(settings_view, ChatViewMsg::Finished)])
+                    }
+                    false => task,
+                }
+            }
+            ChatViewMsg::CopyCodeBlocks { index } => {
+                let blocks = fenced_code_blocks(&self.messages[index].text());
+
+                match blocks.is_empty() {
+                    true => {
+                        self.notice = Some("No code blocks found in the message".to_string());
+
+                        Task::none()
+                    }
+                    false => clipboard::write(blocks.join("\n\n")),
+                }
+            }
+            ChatViewMsg::Retry => {
+                let Some((index, prefix)) = self.failed_run.take() else {
+                    return Task::none();
+                };
+
+                // Only while the failed message is still the last, which the response goes into
+                if !matches!(self.inference_status, InferenceStatus::Idle)
+                    || index + 1 != self.messages.len()
+                {
+                    return Task::none();
+                }
+
+                // The editor's text always ends with a newline
+                let msg = &mut self.messages[index];
+                msg.content =
+                    text_editor::Content::with_text(prefix.strip_suffix('\n').unwrap_or(&prefix));
+                msg.elided = None;
+                self.last_error = None;
+
+                self.update(settings_view, ChatViewMsg::Run)
+            }
+            ChatViewMsg::Continue => {
+                let continuable = self.messages.last().is_some_and(|msg| msg.incomplete);
+
+                if !continuable || !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                self.force_prefill = true;
+
+                self.update(settings_view, ChatViewMsg::Run)
+            }
+            ChatViewMsg::ShowAll { index } => {
+                if matches!(self.inference_status, InferenceStatus::Idle) {
+                    self.messages[index].show_all();
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::CopyAll { index } => clipboard::write(self.messages[index].text()),
+            ChatViewMsg::Fork { .. } | ChatViewMsg::SaveAsTemplate => Task::none(),
+            ChatViewMsg::AddAssistantPrefix => {
+                if matches!(self.inference_status, InferenceStatus::Idle) {
+                    self.messages
+                        .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+                }
+
+                Task::none()
+            }
+            ChatViewMsg::ImportJson => clipboard::read().map(ChatViewMsg::ImportedJson),
+            ChatViewMsg::ImportedJson(json) => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                self.notice = match parse_conversation(json.as_deref().unwrap_or_default()) {
+                    Ok(imported) => {
+                        self.messages = imported
+                            .messages
+                            .into_iter()
+                            .map(UiChatMsg::from_message)
+                            .collect();
+                        self.hidden.clear();
+                        self.remap_indices(|_| None);
+                        self.imported_params = imported.params;
+
+                        if let Some(msg) = self.messages.last_mut() {
+                            msg.incomplete = imported.last_incomplete;
+                        }
+
+                        (!imported.warnings.is_empty()).then(|| imported.warnings.join("\n"))
+                    }
+                    Err(err) => Some(format!("Couldn't import the clipboard as JSON: {err:#}")),
+                };
+
+                Task::none()
+            }
+            ChatViewMsg::CopyJson => match serde_json::to_string_pretty(&self.api_messages()) {
+                Ok(json) => clipboard::write(json),
+                Err(err) => {
+                    self.notice = Some(format!("Couldn't serialize the conversation: {err}"));
+
+                    Task::none()
+                }
+            },
+            ChatViewMsg::CopyPython => clipboard::write(self.python_snippet(settings_view)),
+            ChatViewMsg::SavePython => Task::future(export::save_python_snippet(
+                self.python_snippet(settings_view),
+            ))
+            .map(|res| ChatViewMsg::PythonSaved(res.map_err(|err| err.to_string()))),
+            ChatViewMsg::ExportStats => Task::future(export::save_stats_csv(self.stats.to_csv()))
+                .map(|res| ChatViewMsg::StatsExported(res.map_err(|err| err.to_string()))),
+            ChatViewMsg::StatsExported(res) => {
+                self.notice = Some(match res {
+                    Ok(path) => format!("Exported the session stats to {path}"),
+                    Err(err) => format!("Couldn't export the session stats: {err}"),
+                });
+
+                Task::none()
+            }
+            ChatViewMsg::PythonSaved(res) => {
+                self.notice = Some(match res {
+                    Ok(path) => format!("Saved the Python snippet to {path}"),
+                    Err(err) => format!("Couldn't save the Python snippet: {err}"),
+                });
+
+                Task::none()
+            }
+            ChatViewMsg::FileDropped(path) => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                let is_image = path.extension().is_some_and(|extension| {
+                    ["png", "jpg", "jpeg", "gif", "webp"]
+                        .contains(&extension.to_string_lossy().to_lowercase().as_str())
+                });
+
+                if is_image {
+                    self.notice = Some(format!("Couldn't import {name}, images aren't supported"));
+
+                    return Task::none();
+                }
+
+                Task::future(async move {
+                    ChatViewMsg::FileRead {
+                        name,
+                        contents: tokio::fs::read(path).await.map_err(|err| err.to_string()),
+                    }
+                })
+            }
+            ChatViewMsg::FileRead { name, contents } => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                let text = match contents.map(String::from_utf8) {
+                    Ok(Ok(text)) => text,
+                    Ok(Err(_)) => {
+                        self.notice = Some(format!("Couldn't import {name}, it isn't a text file"));
+
+                        return Task::none();
+                    }
+                    Err(err) => {
+                        self.notice = Some(format!("Couldn't read {name}: {err}"));
+
+                        return Task::none();
+                    }
+                };
+
+                let (text, cut) = cap_text(text, MAX_DROPPED_CHARS);
+
+                if cut {
+                    self.notice = Some(format!(
+                        "{name} was cut to its first {MAX_DROPPED_CHARS} characters"
+                    ));
+                }
+
+                // Into the message being edited, or a new user message
+                let index = match self
+                    .last_focused
+                    .filter(|index| *index < self.messages.len())
+                {
+                    Some(index) => index,
+                    None => {
+                        self.messages.push(UiChatMsg::empty_user());
+                        self.messages.len() - 1
+                    }
+                };
+
+                self.messages[index]
+                    .content
+                    .perform(Action::Edit(Edit::Paste(Arc::new(text))));
+
+                Task::none()
+            }
+            ChatViewMsg::NewConversation => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                let snapshot = self.snapshot(settings_view.settings().saved());
+
+                // The session stats and chat level params outlive the conversation
+                *self = Self {
+                    preset: self.preset.take(),
+                    overrides: std::mem::take(&mut self.overrides),
+                    stats: std::mem::take(&mut self.stats),
+                    fingerprints: std::mem::take(&mut self.fingerprints),
+                    stick_to_bottom: self.stick_to_bottom,
+                    ..Self::new()
+                };
+                self.seed(settings_view.settings().saved());
+
+                // The last message, after the system prompt or the rest of the template
+                let focus = focus::nth(self.messages.len().saturating_sub(1));
+
+                match snapshot
+                    .messages
+                    .iter()
+                    .all(|msg| msg.content.trim().is_empty())
+                {
+                    true => focus,
+                    false => Task::batch([
+                        focus,
+                        Task::future(save_snapshot(snapshot)).map(|res| {
+                            ChatViewMsg::SnapshotSaved(res.map_err(|err| err.to_string()))
+                        }),
+                    ]),
+                }
+            }
+            ChatViewMsg::TestCaseNameChanged(name) => {
+                self.test_case_name = name;
+
+                Task::none()
+            }
+            ChatViewMsg::SaveTestCase => {
+                if self.test_case_name.trim().is_empty() {
+                    return Task::none();
+                }
+
+                let stopped = match self.is_inferencing() {
+                    true => self.update(settings_view, ChatViewMsg::Stop),
+                    false => Task::none(),
+                };
+
+                let saved_settings = settings_view.settings().saved();
+                let test_case = TestCase {
+                    name: self.test_case_name.trim().to_string(),
+                    seed: saved_settings.seed(),
+                    snapshot: self.snapshot(saved_settings),
+                };
+
+                Task::batch([
+                    stopped,
+                    Task::future(save_test_case(test_case)).map(|res| {
+                        ChatViewMsg::TestCaseSaved(res.map_err(|err| format!("{err:#}")))
+                    }),
+                ])
+            }
+            ChatViewMsg::TestCaseSaved(res) => {
+                self.notice = Some(match res {
+                    Ok(path) => {
+                        self.test_case_name.clear();
+
+                        format!("Saved the test case to {path}")
+                    }
+                    Err(err) => format!("Couldn't save the test case: {err}"),
+                });
+
+                Task::none()
+            }
+            ChatViewMsg::SnapshotSaved(res) => {
+                self.notice = Some(match res {
+                    Ok(path) => format!("Saved the previous conversation to {path}"),
+                    Err(err) => format!("Couldn't save the previous conversation: {err}"),
+                });
+
+                Task::none()
+            }
+            // Taken by the playground before getting here
+            ChatViewMsg::ApplyImportedParams | ChatViewMsg::DismissImportedParams => {
+                self.imported_params = None;
+
+                Task::none()
+            }
+            ChatViewMsg::DismissNotice => {
+                self.notice = None;
+
+                Task::none()
+            }
+            ChatViewMsg::SelectPreset(preset) => {
+                if !matches!(self.inference_status, InferenceStatus::Idle) {
+                    return Task::none();
+                }
+
+                if !preset.system_prompt.is_empty() {
+                    let content = text_editor::Content::with_text(&preset.system_prompt);
+
+                    match self.messages.first().map(|msg| msg.role) {
+                        Some(Role::System) => self.messages[0].content = content,
+                        _ => self
+                            .messages
+                            .insert(0, UiChatMsg::new(Role::System, content)),
+                    }
+                }
+
+                self.preset = Some(preset);
+
+                Task::none()
+            }
+            ChatViewMsg::ClearPreset => {
+                self.preset = None;
+
+                Task::none()
+            }
+            ChatViewMsg::ToggleOverrides => {
+                self.show_overrides = !self.show_overrides;
+
+                Task::none()
+            }
+            ChatViewMsg::OverrideModelChanged(model) => {
+                self.overrides.model = model;
+
+                Task::none()
+            }
+            ChatViewMsg::OverrideMaxTokensChanged(max_tokens) => {
+                self.overrides.max_tokens = max_tokens;
+
+                Task::none()
+            }
+            ChatViewMsg::OverrideTemperatureChanged(temperature) => {
+                self.overrides.temperature = temperature;
+
+                Task::none()
+            }
+            ChatViewMsg::ClearOverrides => {
+                self.overrides = Overrides::default();
+
+                Task::none()
+            }
+        }
+    }
+
+    fn message_list(
+        &self,
+        not_inferencing: bool,
+        saved_settings: &SerializedSettings,
+        counter: &tokens::Counter,
+    ) -> Scrollable<ChatViewMsg> {
+        let hidden = (!self.hidden.is_empty()).then(|| {
+            container(
+                text(format!(
+                    "{} earlier messages hidden, they're still sent",
+                    self.hidden.len()
+                ))
+                .size(12)
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().background.weak.text),
+                }),
+            )
+            .center_x(Length::Fill)
+            .into()
+        });
+
+        let trim_boundary = self.trim_boundary(saved_settings);
+
+        scrollable(
+            column(
+                hidden.into_iter().chain(
+                    self.messages
+                        .iter()
+                        .enumerate()
+                        .flat_map(|pair| {
+                            let divider: Option<Element<_>> = trim_boundary
+                                .filter(|(start, _)| *start == pair.0)
+                                .map(|(_, trimmed)| trim_divider(trimmed).into());
+
+                            let waiting_indicator = (pair.0 + 1 == self.messages.len()
+                                && self.inference_status.awaiting_first_token())
+                            .then(|| {
+                                InferenceStatus::WAITING_FRAMES
+                                    [self.waiting_frame % InferenceStatus::WAITING_FRAMES.len()]
+                            });
+
+                            let widget = message_widget(
+                                pair,
+                                not_inferencing,
+                                waiting_indicator,
+                                self.token_count(pair.0, counter),
+                                self.pending_delete == Some(pair.0),
+                                self.pending_regenerate == Some(pair.0),
+                                self.failed_run.as_ref().is_some_and(|(index, _)| {
+                                    *index == pair.0 && *index + 1 == self.messages.len()
+                                }),
+                                pair.1.incomplete && pair.0 + 1 == self.messages.len(),
+                                saved_settings.font_size,
+                                saved_settings.role_badges,
+                            );
+
+                            divider.into_iter().chain(std::iter::once(widget.into()))
+                        })
+                        .chain(std::iter::once(
+                            container(
+                                row([
+                                    match self
+                                        .messages
+                                        .last()
+                                        .is_some_and(|msg| msg.role == Role::Assistant)
+                                    {
+                                        true => button("Reply")
+                                            .on_press_maybe(
+                                                not_inferencing
+                                                    .then_some(ChatViewMsg::AddUserAndFocus),
+                                            )
+                                            .into(),
+                                        false => horizontal_space().width(0).into(),
+                                    },
+                                    button("+ Add Message")
+                                        .on_press_maybe(
+                                            not_inferencing.then_some(ChatViewMsg::AddMessage),
+                                        )
+                                        .style(button::secondary)
+                                        .into(),
+                                ])
+                                .spacing(5),
+                            )
+                            .center_x(Length::Fill)
+                            .into(),
+                        )),
+                ),
+            )
+            .spacing(10),
+        )
+        .id(self.messages_id.clone())
+        .on_scroll(ChatViewMsg::Scrolled)
+        .spacing(3)
+    }
+
+    fn headers_panel(&self) -> Column<ChatViewMsg> {
+        let toggle = button(
+            text(format!(
+                "{} Response headers ({})",
+                if self.show_headers { "▾" } else { "▸" },
+                self.response_headers.len()
+            ))
+            .size(12),
+        )
+        .style(button::text)
+        .on_press(ChatViewMsg::ToggleHeaders);
+
+        let panel = self.show_headers.then(|| {
+            let headers = column(
+                self.response_headers
+                    .iter()
+                    .map(|(name, value)| text(format!("{name}: {value}")).size(12).into()),
+            )
+            .spacing(2);
+
+            scrollable(headers).height(Length::Fixed(150.0))
+        });
+
+        column([toggle.into()]).push_maybe(panel)
+    }
+
+    fn logprobs_panel(&self) -> Column<ChatViewMsg> {
+        let toggle = button(
+            text(format!(
+                "{} Token probabilities ({})",
+                if self.show_logprobs { "▾" } else { "▸" },
+                self.logprobs.len()
+            ))
+            .size(12),
+        )
+        .style(button::text)
+        .on_press(ChatViewMsg::ToggleLogprobs);
+
+        let probability = |logprob: f32| logprob.exp() * 100.0;
+
+        let panel = self.show_logprobs.then(|| {
+            scrollable(column(self.logprobs.iter().map(|logprob| {
+                let alternatives = logprob
+                    .top_logprobs
+                    .iter()
+                    .map(|top| format!("{:?} {:.1}%", top.token, probability(top.logprob)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                text(format!(
+                    "{:?} {:.1}%    {alternatives}",
+                    logprob.token,
+                    probability(logprob.logprob)
+                ))
+                .size(12)
+                .into()
+            })))
+            .height(Length::Fixed(200.0))
+        });
+
+        column([toggle.into()]).push_maybe(panel)
+    }
+
+    fn stats_panel(&self) -> Column<ChatViewMsg> {
+        let toggle = button(
+            text(format!(
+                "{} Session stats ({} runs)",
+                if self.show_stats { "▾" } else { "▸" },
+                self.stats.len()
+            ))
+            .size(12),
+        )
+        .style(button::text)
+        .on_press(ChatViewMsg::ToggleStats);
+
+        let panel = self.show_stats.then(|| {
+            column(self.stats.summaries().into_iter().map(|summary| {
+                let tokens_per_second = match summary.avg_tokens_per_second {
+                    Some(rate) => format!("{rate:.1} tokens/s"),
+                    None => "- tokens/s".to_string(),
+                };
+
+                text(format!(
+                    "{}: {} requests, {:.2}s to first token, {tokens_per_second}, {} tokens",
+                    summary.model,
+                    summary.requests,
+                    summary.avg_time_to_first_token.as_secs_f64(),
+                    summary.total_tokens
+                ))
+                .size(12)
+                .into()
+            }))
+            .push(
+                button(text("Export CSV").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::ExportStats),
+            )
+            .spacing(2)
+        });
+
+        column([toggle.into()]).push_maybe(panel)
+    }
+
+    /// The seed and fingerprint of the last run, if either was there
+    fn reproducibility_panel(&self) -> Option<Column<ChatViewMsg>> {
+        let run = self.reproducibility.as_ref()?;
+
+        if run.seed.is_none() && run.system_fingerprint.is_none() {
+            return None;
+        }
+
+        let summary = text(format!(
+            "Last run: seed {}, system_fingerprint {}",
+            run.seed
+                .map_or("not sent".to_string(), |seed| seed.to_string()),
+            run.system_fingerprint.as_deref().unwrap_or("not returned")
+        ))
+        .size(12);
+
+        let warning = run.fingerprint_changed.then(|| {
+            text(
+                "The fingerprint changed since the last run with this seed, the backend changed \
+                 so the output may differ",
+            )
+            .size(12)
+            .style(|theme: &Theme| text::Style {
+                color: Some(theme.palette().danger),
+            })
+        });
+
+        Some(column([summary.into()]).push_maybe(warning))
+    }
+
+    fn overrides_panel(&self) -> Column<ChatViewMsg> {
+        let toggle = button(
+            text(format!(
+                "{} Overrides{}",
+                if self.show_overrides { "▾" } else { "▸" },
+                if self.overrides.is_empty() {
+                    ""
+                } else {
+                    " (active)"
+                }
+            ))
+            .size(12),
+        )
+        .style(button::text)
+        .on_press(ChatViewMsg::ToggleOverrides);
+
+        let panel = self.show_overrides.then(|| {
+            row([
+                text_input("Model", &self.overrides.model)
+                    .on_input(ChatViewMsg::OverrideModelChanged)
+                    .into(),
+                override_text_input::<u32>(
+                    "Max Tokens",
+                    &self.overrides.max_tokens,
+                    ChatViewMsg::OverrideMaxTokensChanged,
+                )
+                .into(),
+                override_text_input::<f32>(
+                    "Temperature",
+                    &self.overrides.temperature,
+                    ChatViewMsg::OverrideTemperatureChanged,
+                )
+                .into(),
+                button("Clear")
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (!self.overrides.is_empty()).then_some(ChatViewMsg::ClearOverrides),
+                    )
+                    .into(),
+            ])
+            .spacing(5)
+        });
+
+        column([toggle.into()]).push_maybe(panel)
+    }
+
+    pub fn subscription(&self) -> Subscription<ChatViewMsg> {
+        let waiting = match self.inference_status.awaiting_first_token() {
+            true => time::every(Duration::from_millis(300)).map(|_| ChatViewMsg::WaitingTick),
+            false => Subscription::none(),
+        };
+
+        let typewriter = match self.typewriter {
+            Some(_) => time::every(Typewriter::TICK).map(|_| ChatViewMsg::TypewriterTick),
+            None => Subscription::none(),
+        };
+
+        // Restarted for each message awaiting confirmation, gone once nothing is
+        let delete_timeout = match self.pending_delete {
+            Some(index) => time::every(Duration::from_secs(3))
+                .with(index)
+                .map(|(index, _)| ChatViewMsg::CancelDelete { index }),
+            None => Subscription::none(),
+        };
+
+        Subscription::batch([waiting, typewriter, delete_timeout])
+    }
+
+    pub fn view(&self, settings_view: &SettingsView) -> Column<ChatViewMsg> {
+        let not_inferencing = matches!(self.inference_status, InferenceStatus::Idle);
+
+        let settings = settings_view.settings();
+
+        let presets = settings.saved().presets.clone();
+
+        let counter = self.token_counter(settings.saved());
+
+        let prompt_tokens: tokens::Count = (0..self.messages.len())
+            .map(|index| self.token_count(index, &counter))
+            .sum();
+
+        let token_budget = settings.saved().token_budget.parsed().unwrap_or(u32::MAX) as usize;
+
+        let last_msg_assistant = self
+            .messages
+            .last()
+            .is_some_and(|msg| msg.role == Role::Assistant);
+
+        column([
+            container(self.message_list(not_inferencing, settings.saved(), &counter))
+                .style(|_| container::Style {
+                    background: Some(Color::TRANSPARENT.into()),
+                    border: border::rounded(2)
                         .width(1.0)
                         .color(Color::from_rgba8(107, 107, 107, 0.5)),
                     ..Default::default()
@@ -243,33 +3143,1429 @@ impl ChatView {
                 .height(Length::Fill)
                 .padding(5)
                 .into(),
+            match self.logprobs.is_empty() {
+                true => Column::new().into(),
+                false => self.logprobs_panel().into(),
+            },
+            match self.stats.is_empty() {
+                true => Column::new().into(),
+                false => self.stats_panel().into(),
+            },
+            match self.response_headers.is_empty() {
+                true => Column::new().into(),
+                false => self.headers_panel().into(),
+            },
+            match self.reproducibility_panel() {
+                Some(panel) => panel.into(),
+                None => Column::new().into(),
+            },
+            self.overrides_panel().into(),
+            match &self.imported_params {
+                Some(params) => row([
+                    text(format!(
+                        "Generated with {}, temperature {}, max tokens {}",
+                        params.model, params.temperature, params.max_tokens
+                    ))
+                    .width(Length::Fill)
+                    .into(),
+                    button(text("Apply to settings").size(12))
+                        .on_press(ChatViewMsg::ApplyImportedParams)
+                        .into(),
+                    button(text("Dismiss").size(12))
+                        .style(button::secondary)
+                        .on_press(ChatViewMsg::DismissImportedParams)
+                        .into(),
+                ])
+                .spacing(5)
+                .into(),
+                None => Column::new().into(),
+            },
+            match &self.notice {
+                Some(notice) => row([
+                    text(notice.as_str()).width(Length::Fill).into(),
+                    button(text("Dismiss").size(12))
+                        .style(button::secondary)
+                        .on_press(ChatViewMsg::DismissNotice)
+                        .into(),
+                ])
+                .into(),
+                None => Column::new().into(),
+            },
+            row([
+                button(text("New conversation (Ctrl+N)").size(12))
+                    .style(button::secondary)
+                    .on_press_maybe(not_inferencing.then_some(ChatViewMsg::NewConversation))
+                    .into(),
+                button(text("Import JSON from clipboard").size(12))
+                    .style(button::secondary)
+                    .on_press_maybe(not_inferencing.then_some(ChatViewMsg::ImportJson))
+                    .into(),
+                button(text("Copy as JSON").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::CopyJson)
+                    .into(),
+                button(text("Copy as Python").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::CopyPython)
+                    .into(),
+                button(text("Save as Python").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::SavePython)
+                    .into(),
+                button(text("Save as template").size(12))
+                    .style(button::secondary)
+                    .on_press(ChatViewMsg::SaveAsTemplate)
+                    .into(),
+                match settings.saved().drop_trailing_assistant {
+                    false => button(text("Insert assistant prefix").size(12))
+                        .style(button::secondary)
+                        .on_press_maybe(
+                            (not_inferencing && !last_msg_assistant)
+                                .then_some(ChatViewMsg::AddAssistantPrefix),
+                        )
+                        .into(),
+                    true => horizontal_space().width(0).into(),
+                },
+            ])
+            .spacing(5)
+            .into(),
+            row([
+                text_input("Test case name", &self.test_case_name)
+                    .on_input(ChatViewMsg::TestCaseNameChanged)
+                    .on_submit(ChatViewMsg::SaveTestCase)
+                    .size(12)
+                    .into(),
+                button(
+                    text(match not_inferencing {
+                        true => "Save as test case",
+                        false => "Stop and save as test case",
+                    })
+                    .size(12),
+                )
+                .style(button::secondary)
+                .on_press_maybe(
+                    (!self.test_case_name.trim().is_empty()).then_some(ChatViewMsg::SaveTestCase),
+                )
+                .into(),
+            ])
+            .spacing(5)
+            .into(),
             container(row([
                 match self.inference_status {
-                    InferenceStatus::Idle => {
-                        button(container("Run").center_x(Length::Fill)).on_press(ChatViewMsg::Run)
-                    }
+                    InferenceStatus::Idle => button(
+                        container(text(format!("Run ({prompt_tokens} tokens)")))
+                            .center_x(Length::Fill),
+                    )
+                    .style(if prompt_tokens.tokens > token_budget {
+                        button::danger
+                    } else if prompt_tokens.tokens >= token_budget / 10 * 8 {
+                        warning_button
+                    } else {
+                        button::primary
+                    })
+                    .on_press(ChatViewMsg::Run),
                     InferenceStatus::Inferencing { .. } => {
                         button(container("Stop").center_x(Length::Fill))
                             .style(button::danger)
                             .on_press(ChatViewMsg::Stop)
                     }
                 }
-                .into(),
-                button(
-                    checkbox("Stick to Bottom", self.stick_to_bottom)
-                        .on_toggle(ChatViewMsg::StickToBottom),
-                )
-                .style(|_, _| button::Style {
-                    text_color: Color::WHITE,
+                .into(),
+                match self.inference_status {
+                    InferenceStatus::Idle => horizontal_space().width(0).into(),
+                    InferenceStatus::Inferencing { .. } => button(
+                        text(match &self.paused {
+                            Some(held) => format!(
+                                "Resume display (~{} tokens held)",
+                                tokens::estimate(&held.content)
+                            ),
+                            None => "Pause display".to_string(),
+                        })
+                        .size(12),
+                    )
+                    .style(button::secondary)
+                    .padding([7, 10])
+                    .on_press(ChatViewMsg::TogglePaused)
+                    .into(),
+                },
+                {
+                    let model = self.model(settings.saved());
+                    let missing = model.trim().is_empty();
+
+                    container(
+                        text(match missing {
+                            true => "No model set".to_string(),
+                            false => model,
+                        })
+                        .size(12)
+                        .style(move |theme: &Theme| text::Style {
+                            color: Some(match missing {
+                                true => theme.palette().danger,
+                                false => theme.extended_palette().background.weak.text,
+                            }),
+                        }),
+                    )
+                    .padding([7, 5])
+                    .into()
+                },
+                button(
+                    checkbox("Stick to Bottom", self.stick_to_bottom)
+                        .on_toggle(ChatViewMsg::StickToBottom),
+                )
+                .style(|_, _| button::Style {
+                    text_color: Color::WHITE,
+                    ..Default::default()
+                })
+                .into(),
+                pick_list(presets, self.preset.clone(), ChatViewMsg::SelectPreset)
+                    .placeholder("No preset")
+                    .into(),
+                button("Clear")
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (not_inferencing && self.preset.is_some())
+                            .then_some(ChatViewMsg::ClearPreset),
+                    )
+                    .into(),
+                horizontal_space().width(Length::FillPortion(3)).into(),
+            ]))
+            .width(Length::Fill)
+            .height(Length::Shrink)
+            .into(),
+        ])
+        .spacing(5.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use iced::widget::text_editor;
+    use iced::widget::text_editor::{Action, Motion};
+    use iced::Task;
+
+    use crate::chat::{
+        append, cap_text, empty_assistant_turn, fenced_code_blocks, fork_messages,
+        parse_conversation, prefill, scroll_stickiness, test_case_file_name, trim_context,
+        trim_trailing_whitespace, ChatView, ChatViewMsg, InferenceStatus, Reproducibility, Status,
+        Typewriter, UiChatMsg,
+    };
+    use crate::openai::{Delta, Message, Role, ToolCall, ToolCallDelta};
+    use crate::postprocess::PostProcessor;
+    use crate::settings::{Parsable, SerializedSettings, SettingsMessage, SettingsView};
+    use crate::tokens;
+
+    fn messages(roles: &[Role]) -> Vec<Message> {
+        roles
+            .iter()
+            .enumerate()
+            .map(|(index, role)| Message {
+                content: index.to_string(),
+                role: *role,
+                tool_call_id: None,
+                tool_calls: vec![],
+            })
+            .collect()
+    }
+
+    fn contents(messages: Vec<Message>) -> Vec<String> {
+        messages.into_iter().map(|msg| msg.content).collect()
+    }
+
+    #[test]
+    fn code_blocks() {
+        let text = "Here's how:\n\
+            ```rust\n\
+            fn main() {\n\
+            \x20   println!(\"hi\");\n\
+            }\n\
+            ```\n\
+            And then run\n\
+            ```\n\
+            cargo run\n\
+            ```\n\
+            ```python\n\
+            print(\"unclosed\")";
+
+        assert_eq!(
+            fenced_code_blocks(text),
+            [
+                "fn main() {\n    println!(\"hi\");\n}",
+                "cargo run",
+                "print(\"unclosed\")"
+            ]
+        );
+
+        assert!(fenced_code_blocks("No code here").is_empty());
+    }
+
+    #[test]
+    fn fork() {
+        let conversation = messages(&[Role::System, Role::User, Role::Assistant, Role::User]);
+
+        assert_eq!(contents(fork_messages(&conversation, 2)), ["0", "1", "2"]);
+        assert_eq!(
+            contents(fork_messages(&conversation, 3)),
+            ["0", "1", "2", "3"]
+        );
+        assert!(fork_messages(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn context_trimming() {
+        let conversation = messages(&[
+            Role::System,
+            Role::User,
+            Role::Assistant,
+            Role::User,
+            Role::Assistant,
+            Role::User,
+        ]);
+
+        assert_eq!(
+            contents(trim_context(&conversation, 2, &[])),
+            ["0", "4", "5"]
+        );
+
+        for keep_last in [0, 5, 10] {
+            assert_eq!(
+                contents(trim_context(&conversation, keep_last, &[])).len(),
+                6
+            );
+        }
+
+        // Without a leading system message
+        assert_eq!(contents(trim_context(&conversation[1..], 1, &[])), ["5"]);
+
+        // Pinned messages stay in their place
+        assert_eq!(
+            contents(trim_context(&conversation, 2, &[2, 5])),
+            ["0", "2", "4", "5"]
+        );
+
+        // Only a leading system message is kept
+        let conversation = messages(&[Role::User, Role::System, Role::User]);
+        assert_eq!(contents(trim_context(&conversation, 1, &[])), ["2"]);
+    }
+
+    #[test]
+    fn assistant_prefill() {
+        let conversation = messages(&[Role::User, Role::Assistant]);
+
+        assert_eq!(contents(prefill(conversation.clone(), true)), ["0", "1"]);
+        assert_eq!(contents(prefill(conversation, false)), ["0"]);
+
+        let conversation = messages(&[Role::User]);
+
+        assert_eq!(contents(prefill(conversation, false)), ["0"]);
+    }
+
+    #[test]
+    fn empty_assistant_turns() {
+        let conversation = empty_assistant_turn(messages(&[Role::System, Role::User]));
+        assert_eq!(contents(conversation.clone()), ["0", "1", ""]);
+        assert_eq!(conversation[2].role, Role::Assistant);
+
+        // Only after a user message
+        let conversation = messages(&[Role::User, Role::Assistant]);
+        assert_eq!(contents(empty_assistant_turn(conversation)), ["0", "1"]);
+
+        let settings = SerializedSettings {
+            empty_assistant_turn: true,
+            ..Default::default()
+        };
+
+        let mut chat_view = ChatView::new();
+        chat_view.messages[0].content = text_editor::Content::with_text("Hi");
+
+        let req = serde_json::to_value(chat_view.completion_request(&settings)).unwrap();
+        assert_eq!(req["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(req["messages"][1]["role"], "assistant");
+        // Not shown
+        assert_eq!(chat_view.messages.len(), 1);
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        let mut conversation = messages(&[Role::System, Role::User]);
+        conversation[0].content = "Be brief\n\n".to_string();
+        conversation[1].content = "  Hi \t\n".to_string();
+
+        assert_eq!(
+            contents(trim_trailing_whitespace(&conversation)),
+            ["Be brief", "  Hi"]
+        );
+    }
+
+    #[test]
+    fn conversation_params() {
+        let mut chat_view = ChatView::new();
+        chat_view.overrides.temperature = "0.25".to_string();
+
+        let settings = SerializedSettings {
+            model: "saved".to_string(),
+            ..Default::default()
+        };
+
+        let snapshot = serde_json::to_string(&super::Snapshot {
+            params: chat_view.generation_params(&settings),
+            messages: messages(&[Role::User]),
+            last_incomplete: false,
+        })
+        .unwrap();
+
+        let imported = parse_conversation(&snapshot).unwrap();
+        assert_eq!(contents(imported.messages), ["0"]);
+        assert!(imported.warnings.is_empty());
+
+        let params = imported.params.unwrap();
+        assert_eq!(params.model, "saved");
+        assert_eq!(params.temperature, 0.25);
+        assert_eq!(params.max_tokens, 1000);
+
+        // Bare message arrays have no params
+        let imported = parse_conversation(r#"[{"role": "user", "content": "hi"}]"#).unwrap();
+        assert_eq!(contents(imported.messages), ["hi"]);
+        assert!(imported.params.is_none());
+
+        // Other tools' exports
+        let imported = parse_conversation(
+            r#"{"mapping": {"a": {"parent": null, "message": {"author": {"role": "user"},
+                "content": {"content_type": "text", "parts": ["exported"]}}}},
+                "current_node": "a"}"#,
+        )
+        .unwrap();
+        assert_eq!(contents(imported.messages), ["exported"]);
+
+        assert!(parse_conversation(r#"{"params": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_cases() {
+        assert_eq!(
+            test_case_file_name("  Refuses: SQL injection (v2) "),
+            "refuses-sql-injection-v2.json"
+        );
+        // Not a hidden `.json`
+        assert_eq!(test_case_file_name("!!!"), "test-case.json");
+        assert_eq!(test_case_file_name("—"), "test-case.json");
+
+        let mut chat_view = ChatView::new();
+        chat_view.messages[0].content = text_editor::Content::with_text("Hi");
+
+        let test_case = serde_json::to_string(&super::TestCase {
+            name: "Greeting".to_string(),
+            seed: Some(7),
+            snapshot: chat_view.snapshot(&SerializedSettings::default()),
+        })
+        .unwrap();
+
+        // Imports back like any snapshot
+        let imported = parse_conversation(&test_case).unwrap();
+        assert_eq!(imported.messages.len(), 1);
+        assert_eq!(imported.messages[0].content.trim_end(), "Hi");
+        assert!(imported.params.is_some());
+    }
+
+    #[test]
+    fn overrides() {
+        let settings = SerializedSettings {
+            model: "saved".to_string(),
+            ..Default::default()
+        };
+
+        let mut chat_view = ChatView::new();
+
+        chat_view.overrides.model = "override".to_string();
+        chat_view.overrides.max_tokens = "not a number".to_string();
+
+        let req = serde_json::to_value(chat_view.completion_request(&settings)).unwrap();
+        assert_eq!(req["model"], "override");
+        // Invalid overrides fall back to the settings
+        assert_eq!(req["max_tokens"], 1000);
+
+        chat_view.overrides = Default::default();
+        assert!(chat_view.overrides.is_empty());
+
+        chat_view.overrides.model = "  ".to_string();
+        assert!(chat_view.overrides.is_empty());
+
+        assert_eq!(chat_view.completion_request(&settings).model(), "saved");
+        assert_eq!(chat_view.model(&settings), "saved");
+    }
+
+    #[test]
+    fn delete_confirmation() {
+        let mut chat_view = ChatView::new();
+        let settings_view = SettingsView::Loading;
+
+        chat_view.messages = vec![
+            UiChatMsg::new(Role::User, text_editor::Content::with_text("Keep me")),
+            UiChatMsg::empty_user(),
+        ];
+
+        // Empty messages are deleted right away
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 1 });
+        assert_eq!(chat_view.messages.len(), 1);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 0 });
+        assert_eq!(chat_view.pending_delete, Some(0));
+        assert_eq!(chat_view.messages.len(), 1);
+
+        // Pressing something else cancels it
+        let _ = chat_view.update(&settings_view, ChatViewMsg::AddMessage);
+        assert_eq!(chat_view.pending_delete, None);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 0 });
+        let _ = chat_view.update(&settings_view, ChatViewMsg::CancelDelete { index: 0 });
+        assert_eq!(chat_view.pending_delete, None);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 0 });
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 0 });
+        assert_eq!(chat_view.messages.len(), 1);
+        assert_eq!(chat_view.messages[0].content.text().trim(), "");
+    }
+
+    #[test]
+    fn regenerate_from() {
+        let mut chat_view = ChatView::new();
+        let settings_view = SettingsView::Loading;
+
+        chat_view.messages = [Role::User, Role::Assistant, Role::User, Role::Assistant]
+            .into_iter()
+            .map(|role| UiChatMsg::new(role, text_editor::Content::with_text("Turn")))
+            .collect();
+
+        // Discarding the later turns is confirmed first
+        let _ = chat_view.update(&settings_view, ChatViewMsg::RegenerateFrom { index: 0 });
+        assert_eq!(chat_view.pending_regenerate, Some(0));
+        assert_eq!(chat_view.messages.len(), 4);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::RegenerateFrom { index: 0 });
+        assert!(chat_view.is_inferencing());
+
+        // Responded to in a new assistant message
+        assert_eq!(chat_view.messages.len(), 2);
+        assert_eq!(chat_view.messages[0].content.text().trim(), "Turn");
+        assert_eq!(chat_view.messages[1].role, Role::Assistant);
+        assert_eq!(chat_view.messages[1].content.text().trim(), "");
+    }
+
+    #[test]
+    fn cycle_focused_role() {
+        let mut chat_view = ChatView::new();
+        let settings_view = SettingsView::Loading;
+
+        chat_view.messages = vec![
+            UiChatMsg::empty_user(),
+            UiChatMsg::new(Role::Tool, text_editor::Content::new()),
+        ];
+        chat_view.messages[1].tool_call_id = Some("call_1".to_string());
+        chat_view.last_focused = Some(1);
+
+        let roles = (0..4)
+            .map(|_| {
+                let _ = chat_view.update(&settings_view, ChatViewMsg::CycleFocusedRole);
+                chat_view.messages[1].role
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            roles,
+            [Role::System, Role::User, Role::Assistant, Role::System]
+        );
+        assert_eq!(chat_view.messages[1].tool_call_id, None);
+
+        // Locked like the role picker
+        chat_view.inference_status = inferencing();
+        let _ = chat_view.update(&settings_view, ChatViewMsg::CycleFocusedRole);
+        assert_eq!(chat_view.messages[1].role, Role::System);
+    }
+
+    #[test]
+    fn delete_focused() {
+        let mut chat_view = ChatView::new();
+        let settings_view = SettingsView::Loading;
+
+        // Nothing focused yet
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteFocused);
+        assert_eq!(chat_view.messages.len(), 1);
+
+        chat_view.messages = vec![
+            UiChatMsg::empty_user(),
+            UiChatMsg::new(Role::User, text_editor::Content::with_text("Delete me")),
+        ];
+        chat_view.last_focused = Some(1);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteFocused);
+        assert_eq!(chat_view.pending_delete, Some(1));
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteFocused);
+        assert_eq!(chat_view.messages.len(), 1);
+        assert_eq!(chat_view.last_focused, None);
+    }
+
+    #[test]
+    fn dropped_file_cap() {
+        assert_eq!(
+            cap_text("short".to_string(), 10),
+            ("short".to_string(), false)
+        );
+        assert_eq!(
+            cap_text("exact".to_string(), 5),
+            ("exact".to_string(), false)
+        );
+        // Cut on characters rather than bytes
+        assert_eq!(cap_text("héllo".to_string(), 2), ("hé".to_string(), true));
+    }
+
+    #[test]
+    fn empty_response() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view.inference_status = inferencing();
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta {
+                    finish_reason: Some("content_filter".to_string()),
+                    ..Delta::content("")
+                }),
+            },
+        );
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+
+        assert!(matches!(chat_view.inference_status, InferenceStatus::Idle));
+        assert_eq!(
+            chat_view.notice.as_deref(),
+            Some("No content returned, finish_reason: content_filter")
+        );
+        // Not sent back as if the model said it
+        assert!(chat_view.messages[0].content.text().trim().is_empty());
+
+        // Only calling tools is a response
+        let mut chat_view = ChatView::new();
+        chat_view
+            .messages
+            .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+        chat_view.inference_status = inferencing();
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta {
+                    tool_calls: vec![ToolCallDelta {
+                        index: 0,
+                        id: "call_1".to_string(),
+                        name: "weather".to_string(),
+                        arguments: "{}".to_string(),
+                    }],
+                    finish_reason: Some("tool_calls".to_string()),
+                    ..Delta::content("")
+                }),
+            },
+        );
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+
+        assert_eq!(chat_view.notice, None);
+        assert_eq!(chat_view.messages[1].tool_calls.len(), 1);
+        assert!(chat_view.messages[1].content.text().trim().is_empty());
+
+        let mut chat_view = ChatView::new();
+        chat_view.inference_status = inferencing();
+
+        chat_view.received_delta("Hi".to_string(), false);
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+
+        // Content was generated, so there's no note
+        assert!(chat_view.messages[0].content.text().trim().is_empty());
+    }
+
+    #[test]
+    fn new_conversation() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view.overrides.model = "override".to_string();
+        let _ = chat_view.update(&settings_view, ChatViewMsg::AddMessage);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::NewConversation);
+
+        assert_eq!(chat_view.messages.len(), 1);
+        assert_eq!(chat_view.overrides.model, "override");
+    }
+
+    #[test]
+    fn added_messages_are_focused() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::AddMessage);
+        assert_eq!(chat_view.last_focused, Some(1));
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::InsertMessageAbove { index: 1 });
+        assert_eq!(chat_view.messages.len(), 3);
+        assert_eq!(chat_view.last_focused, Some(1));
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::InsertMessageBelow { index: 0 });
+        assert_eq!(chat_view.last_focused, Some(1));
+
+        // What's kept by index follows its message down
+        chat_view.failed_run = Some((2, String::new()));
+        chat_view.pending_delete = Some(2);
+        chat_view.inserted_at(0);
+        assert_eq!(chat_view.failed_run, Some((3, String::new())));
+        assert_eq!(chat_view.last_focused, Some(2));
+        assert_eq!(chat_view.pending_delete, None);
+        chat_view.messages.insert(0, UiChatMsg::empty_user());
+
+        // Not while the editors are locked
+        chat_view.inference_status = inferencing();
+        let _ = chat_view.update(&settings_view, ChatViewMsg::InsertMessageBelow { index: 0 });
+        assert_eq!(chat_view.messages.len(), 5);
+    }
+
+    #[test]
+    fn status() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        assert_eq!(chat_view.status(), Status::Idle);
+
+        chat_view.inference_status = inferencing();
+        assert_eq!(chat_view.status(), Status::Connecting);
+
+        chat_view.received_delta("Hello there".to_string(), false);
+        assert_eq!(
+            chat_view.status(),
+            Status::Streaming {
+                tokens: tokens::Count {
+                    tokens: 3,
+                    exact: false
+                }
+            }
+        );
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Err("Timed out".to_string()),
+            },
+        );
+        assert_eq!(chat_view.status(), Status::Error("Timed out".to_string()));
+    }
+
+    #[test]
+    fn collapse_is_display_only() {
+        let settings_view = SettingsView::Loading;
+
+        let text = (1..=8)
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut chat_view = ChatView::new();
+        chat_view.messages = vec![UiChatMsg::new(
+            Role::User,
+            text_editor::Content::with_text(&text),
+        )];
+
+        assert!(chat_view.messages[0].collapsible());
+        assert!(!UiChatMsg::empty_user().collapsible());
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::ToggleCollapsed { index: 0 });
+        assert!(chat_view.messages[0].collapsed);
+
+        let req = chat_view.completion_request(settings_view.settings().saved());
+        let req = serde_json::to_value(req).unwrap();
+        assert_eq!(
+            req["messages"][0]["content"].as_str().map(str::trim),
+            Some(text.as_str())
+        );
+    }
+
+    #[test]
+    fn stickiness() {
+        // Scrolled to the end
+        assert_eq!(scroll_stickiness(100.0, 500.0, 500.0), Some(true));
+        assert_eq!(scroll_stickiness(100.0, 497.0, 500.0), Some(true));
+        // Scrolled up
+        assert_eq!(scroll_stickiness(500.0, 300.0, 500.0), Some(false));
+        // Scrolled down but not to the end, or the content grew
+        assert_eq!(scroll_stickiness(300.0, 400.0, 500.0), None);
+        assert_eq!(scroll_stickiness(300.0, 300.0, 800.0), None);
+    }
+
+    fn inferencing() -> InferenceStatus {
+        let (_task, abort_handle) = Task::<ChatViewMsg>::none().abortable();
+
+        InferenceStatus::Inferencing {
+            abort_handle: abort_handle.abort_on_drop(),
+            model: "model".to_string(),
+            started: Instant::now(),
+            time_to_first_token: None,
+            generated: String::new(),
+            generated_tokens: tokens::Count::default(),
+            finish_reason: None,
+            counter: tokens::Counter::for_model(""),
+            prefix: String::new(),
+            prompt_tokens: 0,
+            seed: None,
+            system_fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn scrollback() {
+        let mut chat_view = ChatView::new();
+        chat_view.messages = [Role::System, Role::User, Role::Assistant, Role::User]
+            .into_iter()
+            .enumerate()
+            .map(|(index, role)| {
+                UiChatMsg::new(role, text_editor::Content::with_text(&index.to_string()))
+            })
+            .collect();
+        chat_view.last_focused = Some(3);
+
+        chat_view.cap_scrollback(0);
+        assert_eq!(chat_view.messages.len(), 4);
+
+        chat_view.cap_scrollback(2);
+
+        let text = |messages: Vec<Message>| {
+            messages
+                .into_iter()
+                .map(|msg| msg.content.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // The system prompt stays in view
+        assert_eq!(text(chat_view.api_messages()), ["0", "3"]);
+        assert_eq!(text(chat_view.all_messages()), ["0", "1", "2", "3"]);
+        assert_eq!(chat_view.last_focused, Some(1));
+
+        // Pinned messages stay in view too, the hidden messages are still sent in order
+        chat_view.messages[1].pinned = true;
+        chat_view.messages.extend(
+            ["4", "5"].map(|content| {
+                UiChatMsg::new(Role::User, text_editor::Content::with_text(content))
+            }),
+        );
+        chat_view.cap_scrollback(3);
+
+        assert_eq!(text(chat_view.api_messages()), ["0", "3", "5"]);
+        assert_eq!(
+            text(chat_view.all_messages()),
+            ["0", "1", "2", "3", "4", "5"]
+        );
+        assert_eq!(
+            text(
+                chat_view
+                    .completion_request(&SerializedSettings::default())
+                    .messages()
+                    .to_vec()
+            ),
+            ["0", "1", "2", "3", "4", "5"]
+        );
+        assert_eq!(chat_view.pinned(), [3]);
+
+        // Hidden messages stay before the message they were hidden before
+        let _ = chat_view.update(
+            &SettingsView::Loading,
+            ChatViewMsg::InsertMessageAbove { index: 1 },
+        );
+        for _ in 0..2 {
+            let _ = chat_view.update(
+                &SettingsView::Loading,
+                ChatViewMsg::DeleteMessage { index: 3 },
+            );
+        }
+        assert_eq!(
+            text(chat_view.all_messages()),
+            ["0", "1", "2", "", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn trim_boundary() {
+        let mut chat_view = ChatView::new();
+        chat_view.messages = [
+            Role::System,
+            Role::User,
+            Role::Assistant,
+            Role::User,
+            Role::Assistant,
+        ]
+        .into_iter()
+        .map(|role| UiChatMsg::new(role, text_editor::Content::with_text("Turn")))
+        .collect();
+        chat_view.messages[2].pinned = true;
+
+        let settings = |context_window_messages| SerializedSettings {
+            context_window_messages: Parsable::new(context_window_messages),
+            drop_trailing_assistant: true,
+            ..Default::default()
+        };
+
+        assert_eq!(chat_view.trim_boundary(&settings(0)), None);
+
+        // The trailing assistant message isn't sent without prefill, the system prompt and the
+        // pinned message are sent anyway
+        assert_eq!(chat_view.trim_boundary(&settings(2)), Some((2, 1)));
+        assert_eq!(chat_view.trim_boundary(&settings(1)), Some((3, 1)));
+        assert_eq!(chat_view.trim_boundary(&settings(4)), None);
+    }
+
+    #[test]
+    fn tool_messages() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::ChangeRole {
+                index: 0,
+                role: Role::Tool,
+            },
+        );
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::ToolCallIdChanged {
+                index: 0,
+                id: " call_1 ".to_string(),
+            },
+        );
+
+        assert_eq!(
+            chat_view.api_messages()[0].tool_call_id.as_deref(),
+            Some("call_1")
+        );
+
+        // Kept while it's a tool message, dropped once it isn't
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::ChangeRole {
+                index: 0,
+                role: Role::User,
+            },
+        );
+        assert!(chat_view.messages[0].tool_call_id.is_none());
+        assert!(chat_view.api_messages()[0].tool_call_id.is_none());
+
+        // Results of an assistant message's calls go after the results of its earlier calls
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            kind: "function".to_string(),
+            ..Default::default()
+        };
+
+        let mut assistant = UiChatMsg::new(Role::Assistant, text_editor::Content::new());
+        assistant.tool_calls = vec![call("call_1"), call("call_2")];
+        chat_view.messages.push(assistant);
+
+        for call in 0..2 {
+            let _ = chat_view.update(
+                &settings_view,
+                ChatViewMsg::AddToolResult { index: 1, call },
+            );
+        }
+
+        let messages = chat_view.api_messages();
+        assert_eq!(messages[1].tool_calls.len(), 2);
+        assert_eq!(
+            messages[2..]
+                .iter()
+                .map(|msg| (msg.role, msg.tool_call_id.as_deref()))
+                .collect::<Vec<_>>(),
+            [(Role::Tool, Some("call_1")), (Role::Tool, Some("call_2"))]
+        );
+        assert_eq!(chat_view.last_focused, Some(3));
+
+        // Only assistant messages send their calls
+        assert!(messages[0].tool_calls.is_empty());
+    }
+
+    #[test]
+    fn retry() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view.messages.push(UiChatMsg::new(
+            Role::Assistant,
+            text_editor::Content::with_text("partial"),
+        ));
+        chat_view.inference_status = inferencing();
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Err("Timed out".to_string()),
+            },
+        );
+
+        assert_eq!(chat_view.failed_run, Some((1, String::new())));
+        assert!(chat_view.messages[1].text().contains("Timed out"));
+
+        // Follows the failed message around
+        chat_view
+            .messages
+            .insert(0, UiChatMsg::new(Role::System, text_editor::Content::new()));
+        chat_view.failed_run = Some((2, String::new()));
+        let _ = chat_view.update(&settings_view, ChatViewMsg::DeleteMessage { index: 0 });
+        assert_eq!(chat_view.failed_run, Some((1, String::new())));
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Retry);
+
+        // The error and partial response are gone, the run starts over in the same message
+        assert_eq!(chat_view.messages.len(), 2);
+        assert!(chat_view.messages[1].text().trim().is_empty());
+        assert!(chat_view.failed_run.is_none());
+        assert!(chat_view.is_inferencing());
+
+        // Dropped along with the conversation it's in
+        chat_view.stop_inference();
+        chat_view.failed_run = Some((1, String::new()));
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::ImportedJson(Some(r#"[{"role": "user", "content": "hi"}]"#.to_string())),
+        );
+        assert!(chat_view.failed_run.is_none());
+    }
+
+    #[test]
+    fn seed_system_prompt() {
+        let mut chat_view = ChatView::new();
+        chat_view.seed_system_prompt("  ");
+        assert_eq!(chat_view.messages.len(), 1);
+
+        chat_view.seed_system_prompt("Be brief");
+        assert_eq!(chat_view.messages.len(), 2);
+        assert_eq!(chat_view.messages[0].role, Role::System);
+        assert_eq!(chat_view.messages[0].text().trim(), "Be brief");
+
+        // Only new conversations are seeded
+        chat_view.seed_system_prompt("Be brief");
+        assert_eq!(chat_view.messages.len(), 2);
+    }
+
+    #[test]
+    fn conversation_template() {
+        let message = |role, content: &str| Message {
+            content: content.to_string(),
+            role,
+            tool_call_id: None,
+            tool_calls: vec![],
+        };
+
+        let settings = SerializedSettings {
+            default_system_prompt: "Be brief".to_string(),
+            conversation_template: vec![
+                message(Role::System, "Translate to French"),
+                message(Role::User, "Hello"),
+                message(Role::Assistant, "Bonjour"),
+            ],
+            ..Default::default()
+        };
+
+        // Takes precedence over the default system prompt
+        let mut chat_view = ChatView::new();
+        chat_view.seed(&settings);
+        let template = chat_view.template();
+        assert_eq!(template.len(), 3);
+        assert_eq!(template[0].role, Role::System);
+        assert_eq!(template[2].content.trim(), "Bonjour");
+
+        // The prompt follows a template that doesn't end with an empty user message
+        let chat_view = ChatView::from_prompt("Thanks", &settings);
+        assert_eq!(chat_view.messages.len(), 4);
+        assert_eq!(chat_view.messages[3].role, Role::User);
+        assert_eq!(chat_view.messages[3].text().trim(), "Thanks");
+
+        let settings = SerializedSettings {
+            conversation_template: vec![
+                message(Role::System, "Translate to French"),
+                message(Role::User, ""),
+            ],
+            ..settings
+        };
+
+        let chat_view = ChatView::from_prompt("Thanks", &settings);
+        assert_eq!(chat_view.messages.len(), 2);
+        assert_eq!(chat_view.messages[1].text().trim(), "Thanks");
+    }
+
+    #[test]
+    fn stream_line_cap() {
+        let mut msg = UiChatMsg::new(
+            Role::Assistant,
+            text_editor::Content::with_text("1\n2\n3\n4\n5"),
+        );
+
+        msg.cap_lines(5, 2);
+        assert!(msg.elided.is_none());
+
+        msg.cap_lines(4, 2);
+        assert_eq!(msg.elided.as_deref(), Some("1\n2\n3\n"));
+        assert!(msg.content.text().starts_with("[…truncated…]\n4\n5"));
+        assert_eq!(msg.text().trim_end(), "1\n2\n3\n4\n5");
+
+        // Cut again, the marker isn't counted as text
+        append(&mut msg.content, "\n6\n7".to_string());
+        msg.cap_lines(4, 1);
+        assert_eq!(msg.elided.as_deref(), Some("1\n2\n3\n4\n5\n6\n"));
+        assert_eq!(msg.text().trim_end(), "1\n2\n3\n4\n5\n6\n7");
+
+        msg.show_all();
+        assert!(msg.elided.is_none());
+        assert_eq!(msg.content.text().trim_end(), "1\n2\n3\n4\n5\n6\n7");
+    }
+
+    #[test]
+    fn appends_at_the_end() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view.messages = vec![UiChatMsg::new(
+            Role::Assistant,
+            text_editor::Content::with_text("Hello\nworld"),
+        )];
+        chat_view.inference_status = inferencing();
+
+        // As if the user clicked into the start of the message while it streams
+        chat_view.messages[0]
+            .content
+            .perform(Action::Move(Motion::DocumentStart));
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta {
+                    content: " there".to_string(),
                     ..Default::default()
-                })
-                .into(),
-                horizontal_space().width(Length::FillPortion(4)).into(),
-            ]))
-            .width(Length::Fill)
-            .height(Length::Shrink)
-            .into(),
-        ])
-        .spacing(5.0)
+                }),
+            },
+        );
+
+        assert_eq!(
+            chat_view.messages[0].content.text().trim_end(),
+            "Hello\nworld there"
+        );
+    }
+
+    #[test]
+    fn trim_leading_whitespace() {
+        let mut chat_view = ChatView::new();
+        chat_view.inference_status = inferencing();
+
+        assert_eq!(chat_view.received_delta(" \n".to_string(), true), "");
+        assert!(chat_view.inference_status.awaiting_first_token());
+        assert_eq!(
+            chat_view.received_delta("\n Hello".to_string(), true),
+            "Hello"
+        );
+        assert!(!chat_view.inference_status.awaiting_first_token());
+        assert_eq!(
+            chat_view.received_delta(" there".to_string(), true),
+            " there"
+        );
+
+        chat_view.inference_status = inferencing();
+
+        assert_eq!(chat_view.received_delta(" Hi".to_string(), false), " Hi");
+    }
+
+    #[test]
+    fn stop_inference() {
+        let mut chat_view = ChatView::new();
+        chat_view.inference_status = inferencing();
+
+        chat_view.stop_inference();
+
+        assert!(matches!(chat_view.inference_status, InferenceStatus::Idle));
+    }
+
+    #[test]
+    fn token_limit() {
+        let mut chat_view = ChatView::new();
+        assert!(!chat_view.reached_token_limit(1));
+
+        chat_view.inference_status = inferencing();
+        chat_view.received_delta("Hello there".to_string(), false);
+
+        assert!(!chat_view.reached_token_limit(0));
+        assert!(!chat_view.reached_token_limit(4));
+        assert!(chat_view.reached_token_limit(3));
+    }
+
+    #[test]
+    fn incomplete_responses() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view
+            .messages
+            .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+        chat_view.inference_status = inferencing();
+        chat_view.received_delta("Once upon".to_string(), false);
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Stop);
+        assert!(chat_view.messages[1].incomplete);
+
+        // Kept through saving and loading the conversation
+        let snapshot = serde_json::to_string(&super::Snapshot {
+            params: chat_view.generation_params(&SerializedSettings::default()),
+            messages: chat_view.api_messages(),
+            last_incomplete: true,
+        })
+        .unwrap();
+
+        let mut loaded = ChatView::new();
+        let _ = loaded.update(&settings_view, ChatViewMsg::ImportedJson(Some(snapshot)));
+        assert!(loaded.messages[1].incomplete);
+
+        // Continued as a prefill, even though prefilling is off
+        let settings = SerializedSettings {
+            drop_trailing_assistant: true,
+            ..Default::default()
+        };
+        loaded.force_prefill = true;
+        let req = serde_json::to_value(loaded.completion_request(&settings)).unwrap();
+        assert_eq!(req["messages"].as_array().unwrap().len(), 2);
+
+        let _ = loaded.update(&settings_view, ChatViewMsg::Continue);
+        assert!(loaded.is_inferencing());
+        assert_eq!(loaded.messages.len(), 2);
+        assert!(!loaded.messages[1].incomplete);
+        assert!(!loaded.force_prefill);
+
+        // Finished responses aren't marked
+        chat_view.inference_status = inferencing();
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta {
+                    finish_reason: Some("stop".to_string()),
+                    ..Delta::content(" a time")
+                }),
+            },
+        );
+        chat_view.messages[1].incomplete = false;
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+        assert!(!chat_view.messages[1].incomplete);
+
+        // Nor are those whose backend never sent a finish reason
+        chat_view.inference_status = inferencing();
+        chat_view.received_delta("The end".to_string(), false);
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+        assert!(!chat_view.messages[1].incomplete);
+
+        // Unlike those whose stream broke off
+        let recorded = chat_view.stats.len();
+        chat_view.inference_status = inferencing();
+        chat_view.received_delta("The".to_string(), false);
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Err("connection reset".to_string()),
+            },
+        );
+        assert!(chat_view.messages[1].incomplete);
+        assert!(!chat_view.is_inferencing());
+        assert_eq!(chat_view.failed_run, Some((1, String::new())));
+        // Ended like any other run
+        assert_eq!(chat_view.stats.len(), recorded + 1);
+    }
+
+    #[test]
+    fn typewriter() {
+        let mut typewriter = Typewriter {
+            pending: "Héllo there".to_string(),
+            last_typed: Instant::now() - Duration::from_millis(500),
+            finished: false,
+        };
+
+        assert_eq!(typewriter.type_out(10), "Héllo");
+        // Nothing is due right after
+        assert_eq!(typewriter.type_out(10), "");
+        assert_eq!(typewriter.type_out(0), " there");
+
+        let mut settings_view = SettingsView::Loading;
+        let _ = settings_view.update(SettingsMessage::Load(Box::new(SerializedSettings {
+            typewriter_rate: Parsable::new(1000),
+            ..Default::default()
+        })));
+
+        let mut chat_view = ChatView::new();
+        chat_view
+            .messages
+            .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+        chat_view.inference_status = inferencing();
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta::content("Hello")),
+            },
+        );
+        assert!(chat_view.messages[1].text().trim().is_empty());
+
+        // The run only finishes once everything is revealed
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+        assert!(chat_view.is_inferencing());
+
+        std::thread::sleep(Duration::from_millis(10));
+        let _ = chat_view.update(&settings_view, ChatViewMsg::TypewriterTick);
+
+        assert_eq!(chat_view.messages[1].text().trim(), "Hello");
+        assert!(!chat_view.is_inferencing());
+        assert!(chat_view.typewriter.is_none());
+    }
+
+    #[test]
+    fn post_processing() {
+        let mut settings_view = SettingsView::Loading;
+        let _ = settings_view.update(SettingsMessage::Load(Box::new(SerializedSettings {
+            post_processors: vec![PostProcessor::StripThinking],
+            ..Default::default()
+        })));
+
+        let mut chat_view = ChatView::new();
+        chat_view
+            .messages
+            .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+        chat_view.inference_status = inferencing();
+
+        let _ = chat_view.update(
+            &settings_view,
+            ChatViewMsg::Completion {
+                delta: Ok(Delta::content("<think>Greet them</think>\nHello")),
+            },
+        );
+        // Only once it's finished
+        assert!(chat_view.messages[1].text().contains("<think>"));
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+        assert_eq!(chat_view.messages[1].text(), "Hello\n");
+    }
+
+    #[test]
+    fn paused_display() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view
+            .messages
+            .push(UiChatMsg::new(Role::Assistant, text_editor::Content::new()));
+        chat_view.inference_status = inferencing();
+
+        let completion = |content: &str| ChatViewMsg::Completion {
+            delta: Ok(Delta::content(content)),
+        };
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::TogglePaused);
+        let _ = chat_view.update(&settings_view, completion("Hello"));
+        let _ = chat_view.update(&settings_view, completion(" there, friend"));
+
+        // Held back, though still timed
+        assert!(chat_view.messages[1].text().trim().is_empty());
+        assert!(!chat_view.inference_status.awaiting_first_token());
+
+        let _ = chat_view.update(&settings_view, ChatViewMsg::TogglePaused);
+        assert_eq!(chat_view.messages[1].text().trim(), "Hello there, friend");
+        assert!(chat_view.paused.is_none());
+
+        // Pasted before the run ends
+        let _ = chat_view.update(&settings_view, ChatViewMsg::TogglePaused);
+        let _ = chat_view.update(&settings_view, completion("!"));
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Finished);
+
+        assert_eq!(chat_view.messages[1].text().trim(), "Hello there, friend!");
+        assert!(!chat_view.is_inferencing());
+        assert!(chat_view.paused.is_none());
+    }
+
+    #[test]
+    fn fingerprint_changes() {
+        let mut chat_view = ChatView::new();
+
+        let run = chat_view.check_fingerprint(Some(1), Some("fp_a".to_string()));
+        assert!(!run.fingerprint_changed);
+
+        // Only compared between runs with the same seed
+        let run = chat_view.check_fingerprint(Some(2), Some("fp_b".to_string()));
+        assert!(!run.fingerprint_changed);
+        let run = chat_view.check_fingerprint(None, Some("fp_b".to_string()));
+        assert!(!run.fingerprint_changed);
+
+        let run = chat_view.check_fingerprint(Some(1), Some("fp_b".to_string()));
+        assert!(run.fingerprint_changed);
+
+        chat_view.inference_status = inferencing();
+
+        if let InferenceStatus::Inferencing {
+            seed,
+            system_fingerprint,
+            ..
+        } = &mut chat_view.inference_status
+        {
+            *seed = Some(1);
+            *system_fingerprint = Some("fp_b".to_string());
+        }
+
+        chat_view.finish_inference(false);
+
+        assert_eq!(
+            chat_view.reproducibility,
+            Some(Reproducibility {
+                seed: Some(1),
+                system_fingerprint: Some("fp_b".to_string()),
+                fingerprint_changed: false,
+            })
+        );
+    }
+
+    #[test]
+    fn timed_from_the_start_of_the_request() {
+        let settings_view = SettingsView::Loading;
+
+        let mut chat_view = ChatView::new();
+        chat_view.inference_status = inferencing();
+
+        if let InferenceStatus::Inferencing { started, .. } = &mut chat_view.inference_status {
+            *started -= Duration::from_secs(5);
+        }
+
+        // Once out of the queue
+        let _ = chat_view.update(&settings_view, ChatViewMsg::Started);
+
+        let InferenceStatus::Inferencing { started, .. } = &chat_view.inference_status else {
+            panic!("The run ended");
+        };
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn records_stats() {
+        let mut chat_view = ChatView::new();
+
+        // Nothing was generated
+        chat_view.inference_status = inferencing();
+        chat_view.finish_inference(false);
+        assert!(chat_view.stats.is_empty());
+
+        chat_view.inference_status = inferencing();
+        chat_view.received_delta("Hello there".to_string(), false);
+        chat_view.finish_inference(false);
+
+        assert!(matches!(chat_view.inference_status, InferenceStatus::Idle));
+
+        let summaries = chat_view.stats.summaries();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].model, "model");
+        assert_eq!(summaries[0].total_tokens, 3);
     }
 }
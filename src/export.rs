@@ -0,0 +1,171 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::openai::{CompletionRequest, Endpoint};
+
+/// Where exported snippets are saved to
+const EXPORTS_DIR: &str = "exports";
+
+/// A runnable script sending the request with the `openai` Python SDK. The API key is read from
+/// the environment rather than written into it.
+pub fn python_snippet(base_url: &str, endpoint: Endpoint, request: CompletionRequest) -> String {
+    let (create, output, body) = match endpoint {
+        Endpoint::ChatCompletions => (
+            "client.chat.completions.create",
+            "completion.choices[0].message.content",
+            serde_json::to_value(request),
+        ),
+        Endpoint::Completions => (
+            "client.completions.create",
+            "completion.choices[0].text",
+            serde_json::to_value(request.into_text_completion()),
+        ),
+    };
+
+    let mut args = String::new();
+
+    if let Ok(Value::Object(body)) = body {
+        for (name, value) in body {
+            // The snippet prints the whole completion at once
+            if name != "stream" {
+                args.push_str(&format!("    {name}={},\n", python_literal(&value, 1)));
+            }
+        }
+    }
+
+    // The SDK's base URL includes the version, which the requests add to ours
+    let base_url = format!("{}/v1", base_url.trim_end_matches('/'));
+
+    format!(
+        "import os\n\
+         \n\
+         from openai import OpenAI\n\
+         \n\
+         client = OpenAI(\n    \
+             base_url={},\n    \
+             api_key=os.environ[\"OPENAI_API_KEY\"],\n\
+         )\n\
+         \n\
+         completion = {create}(\n\
+         {args}\
+         )\n\
+         \n\
+         print({output})\n",
+        python_literal(&Value::String(base_url), 0)
+    )
+}
+
+/// `value` as a Python literal, nested values are indented by `indent` levels
+fn python_literal(value: &Value, indent: usize) -> String {
+    let pad = "    ".repeat(indent + 1);
+    let end_pad = "    ".repeat(indent);
+
+    match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        Value::Number(number) => number.to_string(),
+        // JSON's escapes are all valid in Python strings
+        Value::String(string) => Value::String(string.clone()).to_string(),
+        Value::Array(items) if items.is_empty() => "[]".to_string(),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| format!("{pad}{},\n", python_literal(item, indent + 1)))
+                .collect::<String>();
+
+            format!("[\n{items}{end_pad}]")
+        }
+        Value::Object(fields) if fields.is_empty() => "{}".to_string(),
+        Value::Object(fields) => {
+            let fields = fields
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{pad}{}: {},\n",
+                        Value::String(key.clone()),
+                        python_literal(value, indent + 1)
+                    )
+                })
+                .collect::<String>();
+
+            format!("{{\n{fields}{end_pad}}}")
+        }
+    }
+}
+
+pub async fn save_python_snippet(snippet: String) -> anyhow::Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("{EXPORTS_DIR}/{timestamp}.py");
+
+    tokio::fs::create_dir_all(EXPORTS_DIR).await?;
+    tokio::fs::write(&path, snippet).await?;
+
+    Ok(path)
+}
+
+pub async fn save_stats_csv(csv: String) -> anyhow::Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("{EXPORTS_DIR}/stats-{timestamp}.csv");
+
+    tokio::fs::create_dir_all(EXPORTS_DIR).await?;
+    tokio::fs::write(&path, csv).await?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::export::{python_literal, python_snippet};
+    use crate::openai::{CompletionRequest, Endpoint, MaxTokensField, Message, Role};
+
+    #[test]
+    fn literals() {
+        assert_eq!(python_literal(&json!(null), 0), "None");
+        assert_eq!(
+            python_literal(&json!([true, false, 1.5]), 0),
+            "[\n    True,\n    False,\n    1.5,\n]"
+        );
+        assert_eq!(
+            python_literal(&json!("Say \"hi\"\n\\ 🙂"), 0),
+            r#""Say \"hi\"\n\\ 🙂""#
+        );
+        assert_eq!(
+            python_literal(&json!([{"a": {}}]), 0),
+            "[\n    {\n        \"a\": {},\n    },\n]"
+        );
+    }
+
+    #[test]
+    fn snippet() {
+        let request = CompletionRequest::new(
+            vec![Message {
+                content: "Write \"\"\"docs\"\"\"".to_string(),
+                role: Role::User,
+                tool_call_id: None,
+                tool_calls: vec![],
+            }],
+            "gpt-4o-mini".to_string(),
+            100,
+            MaxTokensField::MaxTokens,
+            0.5,
+        );
+
+        let snippet = python_snippet(
+            "https://api.openai.com/",
+            Endpoint::ChatCompletions,
+            request,
+        );
+
+        assert!(snippet.contains("base_url=\"https://api.openai.com/v1\","));
+        assert!(snippet.contains("completion = client.chat.completions.create(\n"));
+        assert!(snippet.contains("    model=\"gpt-4o-mini\",\n"));
+        assert!(snippet.contains("\"content\": \"Write \\\"\\\"\\\"docs\\\"\\\"\\\"\","));
+        assert!(snippet.contains("    temperature=0.5,\n"));
+        assert!(!snippet.contains("stream"));
+        assert!(snippet.ends_with("print(completion.choices[0].message.content)\n"));
+    }
+}
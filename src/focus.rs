@@ -0,0 +1,63 @@
+use iced::advanced::widget::operation::{Focusable, Operation};
+use iced::advanced::widget::{self, Id};
+use iced::widget::text_input;
+use iced::{Rectangle, Task};
+
+/// Given to the focusable widgets among the message editors, e.g. the tool call id inputs, so
+/// they aren't counted by [`nth`]
+pub fn skipped_id() -> text_input::Id {
+    text_input::Id::new(SKIPPED)
+}
+
+const SKIPPED: &str = "focus-skipped";
+
+/// Focuses the focusable widget at `index` in layout order and unfocuses the rest.
+///
+/// Text editors don't take an id, so this is how a message's editor gets focused. The message
+/// editors are the first focusable widgets in the window besides those with [`skipped_id`], so
+/// `index` is the message's index.
+pub fn nth<T: Send + 'static>(index: usize) -> Task<T> {
+    widget::operate(FocusNth {
+        target: Some(index),
+        current: 0,
+    })
+}
+
+/// Unfocuses every focusable widget
+pub fn unfocus<T: Send + 'static>() -> Task<T> {
+    widget::operate(FocusNth {
+        target: None,
+        current: 0,
+    })
+}
+
+struct FocusNth {
+    /// `None` to unfocus everything
+    target: Option<usize>,
+    current: usize,
+}
+
+impl<T> Operation<T> for FocusNth {
+    fn focusable(&mut self, state: &mut dyn Focusable, id: Option<&Id>) {
+        if id == Some(&Id::new(SKIPPED)) {
+            state.unfocus();
+            return;
+        }
+
+        match self.target == Some(self.current) {
+            true => state.focus(),
+            false => state.unfocus(),
+        }
+
+        self.current += 1;
+    }
+
+    fn container(
+        &mut self,
+        _id: Option<&Id>,
+        _bounds: Rectangle,
+        operate_on_children: &mut dyn FnMut(&mut dyn Operation<T>),
+    ) {
+        operate_on_children(self)
+    }
+}
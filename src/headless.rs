@@ -0,0 +1,160 @@
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use iced::futures::{StreamExt, TryStreamExt};
+
+use crate::chat::ChatView;
+use crate::openai;
+use crate::openai::{Endpoint, HttpClient, Provider};
+use crate::settings;
+
+/// Runs a single completion of `prompt` with the saved settings, without opening the window
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub prompt: String,
+}
+
+const USAGE: &str = "Usage: playground [--no-gui] --prompt <PROMPT>";
+
+/// `None` without any headless flags, which opens the window as usual. Unknown arguments are
+/// only an error in headless mode, launchers like macOS's add their own (`-psn_...`).
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Args>> {
+    let mut args = args.into_iter();
+
+    let mut headless = false;
+    let mut prompt = None;
+    let mut unknown = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-gui" => headless = true,
+            "--prompt" => {
+                headless = true;
+                prompt = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--prompt needs a value\n{USAGE}"))?,
+                );
+            }
+            _ => {
+                unknown.get_or_insert(arg);
+            }
+        }
+    }
+
+    match (headless, prompt, unknown) {
+        (false, _, _) => Ok(None),
+        (true, _, Some(arg)) => Err(anyhow!("Unknown argument {arg:?}\n{USAGE}")),
+        (true, Some(prompt), None) => Ok(Some(Args { prompt })),
+        (true, None, None) => Err(anyhow!("--no-gui needs a --prompt\n{USAGE}")),
+    }
+}
+
+/// Streams the completion to stdout, returning the exit code
+pub fn run(args: Args) -> i32 {
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(anyhow::Error::from)
+        .and_then(|runtime| runtime.block_on(complete(args)));
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{err:#}");
+
+            1
+        }
+    }
+}
+
+async fn complete(args: Args) -> anyhow::Result<()> {
+    let settings = settings::load_existing_settings()
+        .await
+        .context("Couldn't load the settings")?;
+
+    let req = ChatView::from_prompt(&args.prompt, &settings).completion_request(&settings);
+
+    let mut client = HttpClient::default();
+    client.refresh(&settings.client_options()).await;
+
+    let deltas = match settings.provider {
+        Provider::Mock => openai::mock_completions(req, Duration::ZERO).boxed(),
+        Provider::OpenAiCompatible => {
+            let client = client.get()?;
+
+            match settings.endpoint {
+                Endpoint::ChatCompletions => openai::completions(
+                    &client,
+                    settings.base_url.as_str(),
+                    settings.api_key.as_str(),
+                    req,
+                    &settings.routing(),
+                    settings.connect_retries(),
+                    settings.delta_pointer.clone(),
+                    openai::RawLog::new(&settings.raw_log_path),
+                )
+                .boxed(),
+                Endpoint::Completions => openai::text_completions(
+                    &client,
+                    settings.base_url.as_str(),
+                    settings.api_key.as_str(),
+                    req.into_text_completion(),
+                    &settings.routing(),
+                    settings.connect_retries(),
+                    openai::RawLog::new(&settings.raw_log_path),
+                )
+                .boxed(),
+            }
+        }
+    };
+
+    let mut stdout = std::io::stdout();
+
+    deltas
+        .try_for_each(|delta| {
+            // Flushed per delta so the output streams when piped
+            let written = write!(stdout, "{}", delta.content)
+                .and_then(|_| stdout.flush())
+                .map_err(anyhow::Error::from);
+
+            async move { written }
+        })
+        .await?;
+
+    writeln!(stdout)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headless::{parse_args, Args};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn headless_args() {
+        assert_eq!(parse_args(args(&[])).unwrap(), None);
+
+        let expected = Some(Args {
+            prompt: "Hi there".to_string(),
+        });
+        assert_eq!(
+            parse_args(args(&["--no-gui", "--prompt", "Hi there"])).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_args(args(&["--prompt", "Hi there"])).unwrap(),
+            expected
+        );
+
+        assert!(parse_args(args(&["--no-gui"])).is_err());
+        assert!(parse_args(args(&["--prompt"])).is_err());
+        assert!(parse_args(args(&["--prompt", "Hi", "--verbose"])).is_err());
+        // Left to the window otherwise
+        assert_eq!(parse_args(args(&["-psn_0_12345"])).unwrap(), None);
+    }
+}
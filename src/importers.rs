@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use anyhow::anyhow;
+use serde_json::{Map, Value};
+
+use crate::openai::{Message, Role};
+
+/// Exports of other tools that conversations can be imported from, besides our own snapshots and
+/// OpenAI format message arrays
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    /// `conversations.json` of a ChatGPT data export, or one conversation of it
+    ChatGpt,
+}
+
+impl Format {
+    pub const ALL: &'static [Format] = &[Format::ChatGpt];
+
+    /// Which format `value` is in, if any of them
+    pub fn detect(value: &Value) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.matches(value))
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            Format::ChatGpt => match value {
+                Value::Array(conversations) => conversations
+                    .first()
+                    .is_some_and(|conversation| conversation.get("mapping").is_some()),
+                _ => value.get("mapping").is_some_and(Value::is_object),
+            },
+        }
+    }
+
+    /// Flattens the export into messages, returned with warnings about what was skipped
+    pub fn import(self, value: Value) -> anyhow::Result<(Vec<Message>, Vec<String>)> {
+        match self {
+            Format::ChatGpt => import_chatgpt(value),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::ChatGpt => "ChatGPT export",
+        })
+    }
+}
+
+/// Walks the branch that was last shown, from `current_node` up to the root. Without a current
+/// node the branch ends at the latest message. The messages are in the order of the parent chain,
+/// timestamps only pick the latest message.
+fn import_chatgpt(value: Value) -> anyhow::Result<(Vec<Message>, Vec<String>)> {
+    let mut warnings = vec![];
+
+    let conversation = match value {
+        Value::Array(conversations) if conversations.is_empty() => {
+            return Err(anyhow!("The export has no conversations"));
+        }
+        Value::Array(mut conversations) => {
+            if conversations.len() > 1 {
+                warnings.push(format!(
+                    "Imported the first of {} conversations in the export",
+                    conversations.len()
+                ));
+            }
+
+            conversations.swap_remove(0)
+        }
+        conversation => conversation,
+    };
+
+    let Some(Value::Object(mapping)) = conversation.get("mapping") else {
+        return Err(anyhow!("Expected a mapping of the conversation's messages"));
+    };
+
+    let leaf = conversation
+        .get("current_node")
+        .and_then(Value::as_str)
+        .filter(|id| mapping.contains_key(*id))
+        .map(str::to_string)
+        .or_else(|| latest_node(mapping))
+        .ok_or_else(|| anyhow!("The conversation has no messages"))?;
+
+    // Ids from the leaf to the root, guarding against cycles in malformed exports
+    let mut branch = vec![];
+    let mut visited = HashSet::new();
+    let mut id = Some(leaf);
+
+    while let Some(current) = id.filter(|id| visited.insert(id.clone())) {
+        id = mapping
+            .get(&current)
+            .and_then(|node| node.get("parent"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        branch.push(current);
+    }
+
+    let mut skipped = 0;
+    let mut tool_results = 0;
+
+    let messages = branch
+        .iter()
+        .rev()
+        .filter_map(|id| {
+            mapping
+                .get(id)?
+                .get("message")
+                .filter(|msg| msg.is_object())
+        })
+        .filter_map(|msg| {
+            // Output of ChatGPT's own tools, without the tool calls it answers
+            if msg.pointer("/author/role").and_then(Value::as_str) == Some("tool") {
+                tool_results += 1;
+                return None;
+            }
+
+            let message = chatgpt_message(msg);
+
+            if message.is_none() && !skipped_quietly(msg) {
+                skipped += 1;
+            }
+
+            message
+        })
+        .collect();
+
+    if skipped > 0 {
+        warnings.push(format!(
+            "Skipped {skipped} messages that weren't text or had an unknown role"
+        ));
+    }
+
+    if tool_results > 0 {
+        warnings.push(format!(
+            "Skipped {tool_results} results of ChatGPT's tools like browsing and code execution"
+        ));
+    }
+
+    Ok((messages, warnings))
+}
+
+/// The node whose message was created last
+fn latest_node(mapping: &Map<String, Value>) -> Option<String> {
+    mapping
+        .iter()
+        .filter_map(|(id, node)| Some((id, node.pointer("/message/create_time")?.as_f64()?)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(id, _)| id.clone())
+}
+
+/// ChatGPT's own context like the blank system message every conversation starts with, and
+/// blank messages, aren't worth a warning
+fn skipped_quietly(msg: &Value) -> bool {
+    msg.pointer("/metadata/is_visually_hidden_from_conversation")
+        .and_then(Value::as_bool)
+        .unwrap_or_default()
+        || content_text(msg).is_some_and(|text| text.trim().is_empty())
+}
+
+/// `None` for hidden, blank or non-text messages
+fn chatgpt_message(msg: &Value) -> Option<Message> {
+    if skipped_quietly(msg) {
+        return None;
+    }
+
+    let role = match msg.pointer("/author/role")?.as_str()? {
+        "system" => Role::System,
+        "user" => Role::User,
+        "assistant" => Role::Assistant,
+        _ => return None,
+    };
+
+    Some(Message {
+        content: content_text(msg)?,
+        role,
+        tool_call_id: None,
+        tool_calls: vec![],
+    })
+}
+
+/// The text parts of the content joined, images and other attachments among them are left out
+fn content_text(msg: &Value) -> Option<String> {
+    let content = msg.get("content")?;
+
+    match content.get("content_type")?.as_str()? {
+        "text" | "multimodal_text" => Some(
+            content
+                .get("parts")?
+                .as_array()?
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        "code" => content.get("text")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use crate::importers::Format;
+    use crate::openai::Role;
+
+    fn node(id: &str, parent: Option<&str>, message: Value) -> (String, Value) {
+        (
+            id.to_string(),
+            json!({ "id": id, "parent": parent, "message": message }),
+        )
+    }
+
+    fn text_message(role: &str, text: &str, create_time: f64) -> Value {
+        json!({
+            "author": { "role": role },
+            "content": { "content_type": "text", "parts": [text] },
+            "create_time": create_time,
+        })
+    }
+
+    #[test]
+    fn chatgpt_export() {
+        let mapping = [
+            node("root", None, Value::Null),
+            node(
+                "system",
+                Some("root"),
+                json!({
+                    "author": { "role": "system" },
+                    "content": { "content_type": "text", "parts": [""] },
+                    "metadata": { "is_visually_hidden_from_conversation": true },
+                }),
+            ),
+            node("ask", Some("system"), text_message("user", "Hi", 1.0)),
+            node(
+                "first",
+                Some("ask"),
+                text_message("assistant", "Hello", 2.0),
+            ),
+            // The regenerated answer that was kept
+            node(
+                "second",
+                Some("ask"),
+                text_message("assistant", "Hey there", 3.0),
+            ),
+            node(
+                "browsed",
+                Some("second"),
+                text_message("tool", "Search results", 3.5),
+            ),
+            node(
+                "image",
+                Some("browsed"),
+                json!({
+                    "author": { "role": "user" },
+                    "content": { "content_type": "image_asset_pointer" },
+                    "create_time": 4.0,
+                }),
+            ),
+        ]
+        .into_iter()
+        .collect::<Map<_, _>>();
+
+        let export = json!([
+            { "title": "Greeting", "mapping": mapping, "current_node": "image" },
+            { "title": "Other", "mapping": {} },
+        ]);
+
+        assert_eq!(Format::detect(&export), Some(Format::ChatGpt));
+        assert_eq!(
+            Format::detect(&json!([{ "role": "user", "content": "Hi" }])),
+            None
+        );
+
+        let (messages, warnings) = Format::ChatGpt.import(export).unwrap();
+
+        assert_eq!(
+            messages
+                .iter()
+                .map(|msg| (msg.role, msg.content.as_str()))
+                .collect::<Vec<_>>(),
+            [(Role::User, "Hi"), (Role::Assistant, "Hey there")]
+        );
+        assert_eq!(warnings.len(), 3);
+
+        // Without a current node, the branch of the latest message
+        let (messages, _) = Format::ChatGpt
+            .import(json!({
+                "mapping": {
+                    "ask": { "parent": null, "message": text_message("user", "Hi", 1.0) },
+                    "first": { "parent": "ask", "message": text_message("assistant", "Hello", 2.0) },
+                    "second": { "parent": "ask", "message": text_message("assistant", "Hey", 3.0) },
+                },
+            }))
+            .unwrap();
+
+        assert_eq!(messages[1].content, "Hey");
+    }
+}
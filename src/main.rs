@@ -1,55 +1,523 @@
-use iced::{application, Element, Length, Task, Theme};
-use iced::widget::{container, row, Row};
+use std::time::Duration;
 
-use crate::chat::{ChatView, ChatViewMsg};
-use crate::settings::{SettingsMessage, SettingsView};
+use iced::keyboard::Key;
+use iced::{
+    application, event, keyboard, time, window, Element, Event, Length, Subscription, Task, Theme,
+};
+use iced::widget::{
+    button, center, column, container, horizontal_space, opaque, row, stack, text, Column, Row,
+};
+
+use crate::chat::{ChatView, ChatViewMsg, Status};
+use crate::settings::{SettingsMessage, SettingsView, ThemeMode};
 
 mod chat;
+mod export;
+mod focus;
+mod headless;
+mod importers;
 mod openai;
+mod placeholders;
+mod postprocess;
+mod queue;
+mod schema;
 mod settings;
+mod stats;
+mod tokens;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum PlaygroundMessage {
-    Chat(ChatViewMsg),
+    /// Routed by tab id rather than index so streams keep reaching their tab after others close
+    Chat(usize, ChatViewMsg),
     Settings(SettingsMessage),
+    NewTab,
+    SelectTab(usize),
+    CloseTab(usize),
+    CloseRequested(window::Id),
+    /// The answer to closing with unsaved settings
+    ResolveClose(CloseChoice),
+    ToggleSettings,
+    /// Whether the system prefers dark, polled while following it
+    SystemDark(bool),
+    /// Routed to the active tab, for window wide events like shortcuts
+    ActiveChat(ChatViewMsg),
 }
 
-struct Playground {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Asking before a close would lose unsaved settings
+struct ClosePrompt {
+    window: window::Id,
+    /// Saving failed, the window is kept open so nothing is lost
+    error: Option<String>,
+}
+
+struct ChatTab {
+    id: usize,
     chat_view: ChatView,
+}
+
+struct Playground {
+    tabs: Vec<ChatTab>,
+    active_tab: usize,
+    next_tab_id: usize,
     settings_view: SettingsView,
+    /// Hiding the settings panel gives the chat the full width
+    settings_visible: bool,
+    /// The system's preference for the auto theme
+    system_dark: bool,
+    close_prompt: Option<ClosePrompt>,
+    /// Closed once the settings are saved
+    close_after_save: Option<window::Id>,
+}
+
+fn system_dark() -> bool {
+    // No preference is treated as dark, the default theme
+    !matches!(dark_light::detect(), dark_light::Mode::Light)
 }
 
 impl Playground {
     fn new() -> (Self, Task<PlaygroundMessage>) {
         let (settings_view, task) = SettingsView::new();
 
-        (
-            Self {
-                chat_view: ChatView::new(),
-                settings_view,
-            },
-            task.map(PlaygroundMessage::Settings),
-        )
+        let mut playground = Self {
+            tabs: vec![],
+            active_tab: 0,
+            next_tab_id: 0,
+            settings_view,
+            settings_visible: true,
+            system_dark: system_dark(),
+            close_prompt: None,
+            close_after_save: None,
+        };
+
+        playground.open_tab(ChatView::new());
+
+        (playground, task.map(PlaygroundMessage::Settings))
+    }
+
+    fn open_tab(&mut self, chat_view: ChatView) {
+        let id = self.next_tab_id;
+
+        self.next_tab_id += 1;
+        self.tabs.push(ChatTab { id, chat_view });
+        self.active_tab = id;
+    }
+
+    fn tab(&self, id: usize) -> Option<&ChatTab> {
+        self.tabs.iter().find(|tab| tab.id == id)
     }
 
     fn update(&mut self, message: PlaygroundMessage) -> Task<PlaygroundMessage> {
         match message {
-            PlaygroundMessage::Chat(msg) => self
-                .chat_view
-                .update(&self.settings_view, msg)
-                .map(PlaygroundMessage::Chat),
-            PlaygroundMessage::Settings(msg) => self.settings_view.update(msg),
+            PlaygroundMessage::Chat(id, ChatViewMsg::Fork { index }) => {
+                if let Some(fork) = self.tab(id).map(|tab| tab.chat_view.fork(index)) {
+                    self.open_tab(fork);
+                }
+
+                Task::none()
+            }
+            PlaygroundMessage::Chat(id, ChatViewMsg::ApplyImportedParams) => {
+                let params = self
+                    .tabs
+                    .iter_mut()
+                    .find(|tab| tab.id == id)
+                    .and_then(|tab| tab.chat_view.take_imported_params());
+
+                match params {
+                    Some(params) => self
+                        .settings_view
+                        .update(SettingsMessage::ApplyGenerationParams(Box::new(params))),
+                    None => Task::none(),
+                }
+            }
+            PlaygroundMessage::Chat(id, ChatViewMsg::SaveAsTemplate) => {
+                match self.tab(id).map(|tab| tab.chat_view.template()) {
+                    Some(template) => self
+                        .settings_view
+                        .update(SettingsMessage::ConversationTemplateChanged(template)),
+                    None => Task::none(),
+                }
+            }
+            PlaygroundMessage::Chat(id, msg) => {
+                match self.tabs.iter_mut().find(|tab| tab.id == id) {
+                    Some(tab) => tab
+                        .chat_view
+                        .update(&self.settings_view, msg)
+                        .map(move |msg| PlaygroundMessage::Chat(id, msg)),
+                    None => Task::none(),
+                }
+            }
+            PlaygroundMessage::Settings(msg) => {
+                if let SettingsMessage::Load(settings) = &msg {
+                    self.settings_visible = settings.settings_visible;
+
+                    // The first tab opened before the settings were loaded
+                    for tab in &mut self.tabs {
+                        tab.chat_view.seed(settings);
+                    }
+                }
+
+                let close = match (&msg, self.close_after_save) {
+                    (SettingsMessage::SaveResult(res), Some(id)) => {
+                        self.close_after_save = None;
+
+                        match res {
+                            Ok(_) => Some(id),
+                            Err(err) => {
+                                self.close_prompt = Some(ClosePrompt {
+                                    window: id,
+                                    error: Some(err.clone()),
+                                });
+
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                let task = self.settings_view.update(msg);
+
+                match close {
+                    Some(id) => task.chain(self.close(id)),
+                    None => task,
+                }
+            }
+            PlaygroundMessage::ToggleSettings => {
+                self.settings_visible = !self.settings_visible;
+
+                self.settings_view
+                    .update(SettingsMessage::SettingsVisibleChanged(
+                        self.settings_visible,
+                    ))
+            }
+            PlaygroundMessage::NewTab => {
+                let mut chat_view = ChatView::new();
+                chat_view.seed(self.settings_view.settings().saved());
+
+                self.open_tab(chat_view);
+
+                Task::none()
+            }
+            PlaygroundMessage::SelectTab(id) => {
+                self.active_tab = id;
+
+                Task::none()
+            }
+            PlaygroundMessage::CloseTab(id) => {
+                // Dropping the chat view aborts its inference
+                if let Some(index) = self.tabs.iter().position(|tab| tab.id == id) {
+                    if self.tabs.len() > 1 {
+                        self.tabs.remove(index);
+
+                        if self.active_tab == id {
+                            self.active_tab = self.tabs[index.saturating_sub(1)].id;
+                        }
+                    }
+                }
+
+                Task::none()
+            }
+            PlaygroundMessage::SystemDark(dark) => {
+                self.system_dark = dark;
+
+                Task::none()
+            }
+            PlaygroundMessage::ActiveChat(msg) => {
+                self.update(PlaygroundMessage::Chat(self.active_tab, msg))
+            }
+            PlaygroundMessage::CloseRequested(id) => {
+                let settings = self.settings_view.settings();
+
+                match (settings.unsaved_changes(), settings.autosaves()) {
+                    // Saved now rather than once they'd have settled
+                    (true, true) => {
+                        self.close_after_save = Some(id);
+
+                        self.settings_view.update(SettingsMessage::Save)
+                    }
+                    (true, false) => {
+                        self.close_prompt = Some(ClosePrompt {
+                            window: id,
+                            error: None,
+                        });
+
+                        Task::none()
+                    }
+                    (false, _) => self.close(id),
+                }
+            }
+            PlaygroundMessage::ResolveClose(choice) => {
+                let Some(prompt) = self.close_prompt.take() else {
+                    return Task::none();
+                };
+
+                match choice {
+                    CloseChoice::Save => {
+                        self.close_after_save = Some(prompt.window);
+
+                        self.settings_view.update(SettingsMessage::Save)
+                    }
+                    CloseChoice::Discard => self.close(prompt.window),
+                    CloseChoice::Cancel => Task::none(),
+                }
+            }
+        }
+    }
+
+    fn close(&mut self, id: window::Id) -> Task<PlaygroundMessage> {
+        self.shutdown();
+
+        window::close(id)
+    }
+
+    /// Cleans up before the window closes
+    fn shutdown(&mut self) {
+        for tab in &mut self.tabs {
+            tab.chat_view.stop_inference();
         }
     }
 
-    fn view(&self) -> Row<PlaygroundMessage> {
-        row([
-            container(Element::from(self.chat_view.view()).map(PlaygroundMessage::Chat))
-                .width(Length::FillPortion(3))
-                .padding(5.0)
+    fn subscription(&self) -> Subscription<PlaygroundMessage> {
+        // There's no notification of the system theme changing, so it's polled
+        let system_theme = match self.settings_view.settings().saved().theme {
+            ThemeMode::Auto => time::every(Duration::from_secs(2))
+                .map(|_| PlaygroundMessage::SystemDark(system_dark())),
+            ThemeMode::Dark | ThemeMode::Light => Subscription::none(),
+        };
+
+        Subscription::batch(
+            [
+                window::close_requests().map(PlaygroundMessage::CloseRequested),
+                system_theme,
+                self.settings_view
+                    .subscription()
+                    .map(PlaygroundMessage::Settings),
+                event::listen_with(|event, _, _| match event {
+                    Event::Window(window::Event::FileDropped(path)) => Some(
+                        PlaygroundMessage::ActiveChat(ChatViewMsg::FileDropped(path)),
+                    ),
+                    _ => None,
+                }),
+                keyboard::on_key_press(|key, modifiers| match key.as_ref() {
+                    Key::Character("n") if modifiers.command() => {
+                        Some(PlaygroundMessage::ActiveChat(ChatViewMsg::NewConversation))
+                    }
+                    // Not backspace, focused editors take it before it gets here
+                    Key::Character("d") if modifiers.command() => {
+                        Some(PlaygroundMessage::ActiveChat(ChatViewMsg::DeleteFocused))
+                    }
+                    Key::Character("r") if modifiers.command() => {
+                        Some(PlaygroundMessage::ActiveChat(ChatViewMsg::CycleFocusedRole))
+                    }
+                    Key::Character(",") if modifiers.command() => {
+                        Some(PlaygroundMessage::ToggleSettings)
+                    }
+                    _ => None,
+                }),
+            ]
+            .into_iter()
+            .chain(self.tabs.iter().map(|tab| {
+                tab.chat_view
+                    .subscription()
+                    .with(tab.id)
+                    .map(|(id, msg)| PlaygroundMessage::Chat(id, msg))
+            })),
+        )
+    }
+
+    fn tab_bar(&self) -> Row<PlaygroundMessage> {
+        row(self
+            .tabs
+            .iter()
+            .map(|tab| {
+                let label = match tab.chat_view.is_inferencing() {
+                    true => format!("Chat {} •", tab.id + 1),
+                    false => format!("Chat {}", tab.id + 1),
+                };
+
+                row([
+                    button(text(label).size(14))
+                        .style(if tab.id == self.active_tab {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .on_press(PlaygroundMessage::SelectTab(tab.id))
+                        .into(),
+                    button(text("×").size(14))
+                        .style(button::secondary)
+                        .on_press_maybe(
+                            (self.tabs.len() > 1).then_some(PlaygroundMessage::CloseTab(tab.id)),
+                        )
+                        .into(),
+                ])
+                .into()
+            })
+            .chain([
+                button(text("+").size(14))
+                    .style(button::secondary)
+                    .on_press(PlaygroundMessage::NewTab)
+                    .into(),
+                horizontal_space().into(),
+                button(
+                    text(match self.settings_visible {
+                        true => "Hide settings (Ctrl+,)",
+                        false => "Show settings (Ctrl+,)",
+                    })
+                    .size(14),
+                )
+                .style(button::secondary)
+                .on_press(PlaygroundMessage::ToggleSettings)
                 .into(),
-            Element::from(self.settings_view.view()).map(PlaygroundMessage::Settings),
-        ])
+            ]))
+        .spacing(5)
+    }
+
+    fn close_prompt(&self, prompt: &ClosePrompt) -> Element<PlaygroundMessage> {
+        let can_save = self.settings_view.settings().valid_inputs();
+
+        let mut dialog = Column::new()
+            .push(text("Save the settings before closing?").size(18))
+            .push(text("Your changes to the settings haven't been saved.").size(14));
+
+        if let Some(err) = &prompt.error {
+            dialog = dialog.push(text(format!("Saving failed: {err}")).size(14).style(
+                |theme: &Theme| text::Style {
+                    color: Some(theme.palette().danger),
+                },
+            ));
+        }
+
+        if !can_save {
+            dialog = dialog.push(text("Some values are invalid and can't be saved.").size(14));
+        }
+
+        let dialog = dialog
+            .push(
+                row([
+                    button(text("Save").size(14))
+                        .on_press_maybe(
+                            can_save.then_some(PlaygroundMessage::ResolveClose(CloseChoice::Save)),
+                        )
+                        .into(),
+                    button(text("Discard").size(14))
+                        .style(button::danger)
+                        .on_press(PlaygroundMessage::ResolveClose(CloseChoice::Discard))
+                        .into(),
+                    horizontal_space().into(),
+                    button(text("Cancel").size(14))
+                        .style(button::secondary)
+                        .on_press(PlaygroundMessage::ResolveClose(CloseChoice::Cancel))
+                        .into(),
+                ])
+                .spacing(5),
+            )
+            .spacing(10);
+
+        // Opaque so the chat underneath can't be clicked while asking
+        opaque(
+            center(
+                container(dialog)
+                    .width(400)
+                    .padding(15)
+                    .style(container::rounded_box),
+            )
+            .style(|theme: &Theme| container::Style {
+                background: Some(
+                    iced::Color {
+                        a: 0.6,
+                        ..theme.palette().background
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            }),
+        )
+    }
+
+    fn view(&self) -> Element<PlaygroundMessage> {
+        let tab = self.tab(self.active_tab).unwrap_or(&self.tabs[0]);
+        let id = tab.id;
+
+        let status = tab.chat_view.status();
+        let is_error = matches!(status, Status::Error(_));
+
+        let status_bar = container(text(status.to_string()).size(12).style(
+            move |theme: &Theme| text::Style {
+                color: Some(match is_error {
+                    true => theme.palette().danger,
+                    false => theme.extended_palette().background.weak.text,
+                }),
+            },
+        ))
+        .padding([2, 5]);
+
+        let mut panes = row([container(
+            column([
+                self.tab_bar().into(),
+                Element::from(tab.chat_view.view(&self.settings_view))
+                    .map(move |msg| PlaygroundMessage::Chat(id, msg)),
+            ])
+            .spacing(5),
+        )
+        .width(Length::FillPortion(3))
+        .padding(5.0)
+        .into()]);
+
+        if self.settings_visible {
+            let running = self
+                .tabs
+                .iter()
+                .filter(|tab| tab.chat_view.is_inferencing())
+                .count();
+
+            panes = panes.push(
+                Element::from(self.settings_view.view(running)).map(PlaygroundMessage::Settings),
+            );
+        }
+
+        let panes = panes.height(Length::Fill);
+
+        let content = column([panes.into(), status_bar.into()]);
+
+        match &self.close_prompt {
+            Some(prompt) => stack([content.into(), self.close_prompt(prompt)]).into(),
+            None => content.into(),
+        }
+    }
+
+    /// Tells windows for different providers apart, follows the active tab's model
+    fn title(&self) -> String {
+        let settings = self.settings_view.settings();
+        let saved_settings = settings.saved();
+
+        let model = self
+            .tab(self.active_tab)
+            .map(|tab| tab.chat_view.model(saved_settings))
+            .unwrap_or_default();
+
+        match model.trim() {
+            "" => format!("Playground — {}", saved_settings.provider_label()),
+            model => format!("Playground — {} · {model}", saved_settings.provider_label()),
+        }
+    }
+
+    fn theme(&self) -> Theme {
+        match self.settings_view.settings().saved().theme {
+            ThemeMode::Dark => Theme::Dark,
+            ThemeMode::Light => Theme::Light,
+            ThemeMode::Auto => match self.system_dark {
+                true => Theme::Dark,
+                false => Theme::Light,
+            },
+        }
     }
 
     fn scale_factor(&self) -> f64 {
@@ -58,8 +526,59 @@ impl Playground {
 }
 
 fn main() -> iced::Result {
-    application("Playground", Playground::update, Playground::view)
+    match headless::parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => std::process::exit(headless::run(args)),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    }
+
+    application(Playground::title, Playground::update, Playground::view)
+        .subscription(Playground::subscription)
+        .window(window::Settings {
+            exit_on_close_request: false,
+            ..Default::default()
+        })
         .scale_factor(Playground::scale_factor)
-        .theme(|_| Theme::Dark)
+        .theme(Playground::theme)
         .run_with(Playground::new)
 }
+
+#[cfg(test)]
+mod tests {
+    use iced::window;
+
+    use crate::settings::{SerializedSettings, SettingsMessage};
+    use crate::{Playground, PlaygroundMessage};
+
+    #[test]
+    fn close_flushes_autosave() {
+        let settings = PlaygroundMessage::Settings;
+
+        let (mut playground, _) = Playground::new();
+        let _ = playground.update(settings(SettingsMessage::Load(Box::new(
+            SerializedSettings {
+                autosave: true,
+                ..Default::default()
+            },
+        ))));
+        let _ = playground.update(settings(SettingsMessage::ModelChanged("gpt".to_string())));
+
+        // Saved before closing instead of asking
+        let window = window::Id::unique();
+        let _ = playground.update(PlaygroundMessage::CloseRequested(window));
+        assert!(playground.close_prompt.is_none());
+        assert_eq!(playground.close_after_save, Some(window));
+
+        // Invalid settings wouldn't be autosaved, so it asks
+        playground.close_after_save = None;
+        let _ = playground.update(settings(SettingsMessage::HttpProxyChanged(
+            "http://[::1".to_string(),
+        )));
+        let _ = playground.update(PlaygroundMessage::CloseRequested(window));
+        assert!(playground.close_prompt.is_some());
+        assert_eq!(playground.close_after_save, None);
+    }
+}
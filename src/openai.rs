@@ -1,12 +1,14 @@
-use anyhow::anyhow;
-use iced::futures::{Stream, StreamExt, TryStreamExt};
-use reqwest::header::AUTHORIZATION;
-use reqwest_eventsource::{Event, RequestBuilderExt};
+use anyhow::{anyhow, Context};
+use iced::futures::stream::BoxStream;
+use iced::futures::{stream, Stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fmt::{Debug, Display, Formatter};
 use std::future;
-use std::sync::OnceLock;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -14,6 +16,8 @@ pub enum Role {
     System,
     User,
     Assistant,
+    /// The result of one of the tool calls of an earlier assistant message
+    Tool,
 }
 
 impl Display for Role {
@@ -22,108 +26,2050 @@ impl Display for Role {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub content: String,
     pub role: Role,
+    /// The call a tool message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Made by an assistant message, each answered by a later tool message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    /// Only `function` so far
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON, though models don't always produce valid JSON
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// Part of a streamed tool call, the calls are streamed in pieces that share their index
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+impl ToolCallDelta {
+    fn parse(index: usize, value: &Value) -> Self {
+        let field = |pointer: &str| {
+            value
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        Self {
+            index: value
+                .get("index")
+                .and_then(Value::as_u64)
+                .map_or(index, |index| index as usize),
+            id: field("/id"),
+            name: field("/function/name"),
+            arguments: field("/function/arguments"),
+        }
+    }
+}
+
+/// Pieces the streamed tool calls together, a piece of a call not seen yet starts it
+pub fn append_tool_calls(calls: &mut Vec<ToolCall>, deltas: Vec<ToolCallDelta>) {
+    for delta in deltas {
+        if calls.len() <= delta.index {
+            calls.resize_with(delta.index + 1, || ToolCall {
+                kind: function_type(),
+                ..Default::default()
+            });
+        }
+
+        let call = &mut calls[delta.index];
+
+        call.id.push_str(&delta.id);
+        call.function.name.push_str(&delta.name);
+        call.function.arguments.push_str(&delta.arguments);
+    }
+}
+
+/// Parses an OpenAI format messages array, skipping (with a warning) messages that don't fit
+/// e.g. unknown roles. Multimodal content is flattened into its text parts.
+pub fn parse_messages(json: &str) -> anyhow::Result<(Vec<Message>, Vec<String>)> {
+    Ok(parse_message_values(serde_json::from_str(json)?))
+}
+
+/// Like `parse_messages`, from an already parsed array
+pub fn parse_message_values(values: Vec<Value>) -> (Vec<Message>, Vec<String>) {
+    let mut messages = vec![];
+    let mut warnings = vec![];
+
+    for (index, mut value) in values.into_iter().enumerate() {
+        // Assistant messages that only make tool calls have no content
+        if value.get("content").is_some_and(Value::is_null) {
+            value["content"] = Value::String(String::new());
+        }
+
+        if let Some(parts) = value.get("content").and_then(Value::as_array) {
+            let text = parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            value["content"] = Value::String(text);
+        }
+
+        match serde_json::from_value::<Message>(value) {
+            Ok(message) => messages.push(message),
+            Err(err) => warnings.push(format!("Skipped message {index}: {err}")),
+        }
+    }
+
+    (messages, warnings)
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    #[default]
+    OpenAiCompatible,
+    /// Echoes the last message back without making any requests
+    Mock,
+}
+
+impl Provider {
+    pub const ALL: &'static [Provider] = &[Provider::OpenAiCompatible, Provider::Mock];
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Provider::OpenAiCompatible => "OpenAI Compatible",
+            Provider::Mock => "Mock (offline)",
+        })
+    }
+}
+
+/// Which API the requests are sent to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    #[default]
+    ChatCompletions,
+    /// The legacy `v1/completions`, sent the conversation as a single prompt
+    Completions,
+}
+
+impl Endpoint {
+    pub const ALL: &'static [Endpoint] = &[Endpoint::ChatCompletions, Endpoint::Completions];
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Endpoint::ChatCompletions => "Chat (v1/chat/completions)",
+            Endpoint::Completions => "Completion (v1/completions)",
+        })
+    }
+}
+
+/// Which field name the token limit is sent under, newer OpenAI models only accept
+/// `max_completion_tokens` while most compatible backends still expect `max_tokens`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxTokensField {
+    #[default]
+    MaxTokens,
+    MaxCompletionTokens,
+}
+
+impl MaxTokensField {
+    pub const ALL: &'static [MaxTokensField] = &[
+        MaxTokensField::MaxTokens,
+        MaxTokensField::MaxCompletionTokens,
+    ];
+}
+
+impl Display for MaxTokensField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MaxTokensField::MaxTokens => "max_tokens",
+            MaxTokensField::MaxCompletionTokens => "max_completion_tokens",
+        })
+    }
+}
+
+/// How the stop sequences are sent, most backends take an array but some only a single string
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopFormat {
+    #[default]
+    Array,
+    /// One string with the sequences joined by newlines
+    Joined,
+}
+
+impl StopFormat {
+    pub const ALL: &'static [StopFormat] = &[StopFormat::Array, StopFormat::Joined];
+}
+
+impl Display for StopFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StopFormat::Array => "Array",
+            StopFormat::Joined => "Joined by newlines",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stop {
+    Array(Vec<String>),
+    Joined(String),
+}
+
+/// Flattened into the request so exactly one of the two fields is emitted
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MaxTokens {
+    MaxTokens(u32),
+    MaxCompletionTokens(u32),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompletionRequest {
     messages: Vec<Message>,
     model: String,
-    max_tokens: u32,
+    #[serde(flatten)]
+    max_tokens: MaxTokens,
     stream: bool,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+    /// Stable end user identifier for abuse monitoring
+    #[serde(skip_serializing_if = "String::is_empty")]
+    user: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    /// Best effort determinism, as long as the `system_fingerprint` stays the same
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Stop>,
 }
 
 impl CompletionRequest {
-    pub fn new(messages: Vec<Message>, model: String, max_tokens: u32, temperature: f32) -> Self {
+    pub fn new(
+        messages: Vec<Message>,
+        model: String,
+        max_tokens: u32,
+        max_tokens_field: MaxTokensField,
+        temperature: f32,
+    ) -> Self {
         Self {
             messages,
             model,
-            max_tokens,
+            max_tokens: match max_tokens_field {
+                MaxTokensField::MaxTokens => MaxTokens::MaxTokens(max_tokens),
+                MaxTokensField::MaxCompletionTokens => MaxTokens::MaxCompletionTokens(max_tokens),
+            },
             stream: true,
             temperature,
+            logprobs: None,
+            top_logprobs: None,
+            user: "".to_string(),
+            response_format: None,
+            seed: None,
+            stop: None,
+        }
+    }
+
+    /// Requests the logprobs of each generated token along with the `top_logprobs` most likely
+    /// alternatives
+    pub fn with_logprobs(mut self, top_logprobs: u8) -> Self {
+        self.logprobs = Some(true);
+        self.top_logprobs = (top_logprobs > 0).then_some(top_logprobs);
+        self
+    }
+
+    /// Not sent if blank
+    pub fn with_user(mut self, user: String) -> Self {
+        self.user = user;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: Option<i64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn seed(&self) -> Option<i64> {
+        self.seed
+    }
+
+    /// Not sent if there are none
+    pub fn with_stop(mut self, stop: Vec<String>, format: StopFormat) -> Self {
+        self.stop = match format {
+            _ if stop.is_empty() => None,
+            StopFormat::Array => Some(Stop::Array(stop)),
+            StopFormat::Joined => Some(Stop::Joined(stop.join("\n"))),
+        };
+        self
+    }
+
+    /// Structured outputs, the response is constrained to the schema
+    pub fn with_json_schema(mut self, schema: Value) -> Self {
+        self.response_format = Some(serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": "response",
+                "strict": true,
+                "schema": schema,
+            },
+        }));
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The request for the legacy completions endpoint, with the message contents concatenated
+    /// into the prompt so the response continues the text
+    pub fn into_text_completion(self) -> TextCompletionRequest {
+        TextCompletionRequest {
+            prompt: self.messages.into_iter().map(|msg| msg.content).collect(),
+            model: self.model,
+            max_tokens: match self.max_tokens {
+                MaxTokens::MaxTokens(max_tokens) => max_tokens,
+                MaxTokens::MaxCompletionTokens(max_tokens) => max_tokens,
+            },
+            stream: self.stream,
+            temperature: self.temperature,
+            user: self.user,
+            seed: self.seed,
+            stop: self.stop,
         }
     }
 }
 
-/// Returns a completions stream with the completion delta as each item
-pub fn completions(
-    base_url: &str,
-    api_key: &str,
-    request: CompletionRequest,
-) -> impl Stream<Item = anyhow::Result<String>> {
-    const COMPLETIONS_PATH: &str = "v1/chat/completions";
+/// Body of a legacy completions request, which only accepts `max_tokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextCompletionRequest {
+    prompt: String,
+    model: String,
+    max_tokens: u32,
+    stream: bool,
+    temperature: f32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    user: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Stop>,
+}
 
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f32,
+}
 
-    let client = CLIENT.get_or_init(reqwest::Client::new);
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
 
-    let url = match base_url.chars().last() {
-        Some('/') => format!("{base_url}{COMPLETIONS_PATH}"),
-        _ => format!("{base_url}/{COMPLETIONS_PATH}"),
-    };
+/// A streamed chunk of the completion
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Delta {
+    pub content: String,
+    /// Streamed before the content by reasoning models e.g. DeepSeek-R1
+    pub reasoning: String,
+    /// Why the generation stopped, only sent with the last delta
+    pub finish_reason: Option<String>,
+    /// Only present if requested
+    pub logprobs: Vec<TokenLogprob>,
+    /// Of the backend configuration that served the request, not every provider sends one
+    pub system_fingerprint: Option<String>,
+    /// Of the response, only sent with the first delta
+    pub headers: Vec<(String, String)>,
+    pub tool_calls: Vec<ToolCallDelta>,
+}
 
-    client
-        .post(url)
-        .json(&request)
-        .header(AUTHORIZATION, format!("Bearer {api_key}"))
-        .eventsource()
-        .unwrap() // Impossible
-        .take_while(|res| {
-            future::ready(!matches!(res, Err(reqwest_eventsource::Error::StreamEnded)))
-        })
-        .map_err(Into::into)
-        .try_filter_map(|event| async move {
-            Ok(match event {
-                Event::Message(event) => {
-                    if event.data.as_str() == "[DONE]" {
-                        return Ok(None);
-                    }
+impl Delta {
+    pub fn content(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Appends a later delta onto this one
+    pub fn merge(&mut self, other: Delta) {
+        self.content.push_str(&other.content);
+        self.reasoning.push_str(&other.reasoning);
+        self.finish_reason = other.finish_reason.or(self.finish_reason.take());
+        self.logprobs.extend(other.logprobs);
+        self.system_fingerprint = other.system_fingerprint.or(self.system_fingerprint.take());
+        self.headers.extend(other.headers);
+        self.tool_calls.extend(other.tool_calls);
+    }
+}
+
+/// Streamed back from a request, the headers come before any of the events
+#[derive(Debug, Clone, PartialEq)]
+enum Received {
+    Headers(Vec<(String, String)>),
+    Event(SseEvent),
+}
 
-                    let value = serde_json::from_str::<Value>(event.data.as_str())?;
-                    Some(
-                        value
-                            .pointer("/choices/0/delta/content")
-                            .and_then(Value::as_str)
-                            .map(str::to_string)
-                            .ok_or_else(|| anyhow!("Delta not found within:\n{value:#}"))?,
-                    )
+/// A server-sent event, only the fields the completions stream uses
+#[derive(Debug, Clone, PartialEq)]
+struct SseEvent {
+    event: String,
+    data: String,
+}
+
+/// Decodes UTF-8 from bytes that may split a character across chunks
+#[derive(Debug, Default)]
+struct Utf8Buffer {
+    /// The start of a character completed by the next chunk
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    /// Returns the complete characters so far, invalid bytes are replaced like with
+    /// `String::from_utf8_lossy`
+    fn push(&mut self, bytes: &[u8]) -> String {
+        let mut text = String::new();
+
+        self.pending.extend_from_slice(bytes);
+
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+
+                    return text;
                 }
-                _ => None,
-            })
+                Err(err) => {
+                    let valid_up_to = err.valid_up_to();
+
+                    text.push_str(
+                        std::str::from_utf8(&self.pending[..valid_up_to]).unwrap_or_default(),
+                    );
+
+                    match err.error_len() {
+                        // Cut off at the end, the rest is in the next chunk
+                        None => {
+                            self.pending.drain(..valid_up_to);
+
+                            return text;
+                        }
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid_up_to + len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Incrementally parses server-sent events from text that may be split anywhere across chunks
+#[derive(Debug, Default)]
+struct SseParser {
+    utf8: Utf8Buffer,
+    /// The incomplete line at the end of the last chunk
+    line: String,
+    event: String,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Returns the events completed by the chunk, characters split across chunks are kept until
+    /// they're complete
+    fn feed_bytes(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let text = self.utf8.push(chunk);
+
+        self.feed(&text)
+    }
+
+    /// Returns the events completed by the chunk
+    fn feed(&mut self, chunk: &str) -> Vec<SseEvent> {
+        let mut events = vec![];
+
+        self.line.push_str(chunk);
+
+        while let Some(end) = self.line.find('\n') {
+            let line = self.line.drain(..=end).collect::<String>();
+
+            events.extend(self.parse_line(line.trim_end_matches(['\n', '\r'])));
+        }
+
+        events
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            let data = std::mem::take(&mut self.data);
+
+            return (!data.is_empty()).then(|| SseEvent {
+                event: match event.is_empty() {
+                    true => "message".to_string(),
+                    false => event,
+                },
+                data: data.join("\n"),
+            });
+        }
+
+        // Lines starting with a colon are comments, often sent as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// Parses a completions stream event with `parse_data`, `None` if it doesn't carry a delta e.g.
+/// keep-alive pings
+fn parse_event(
+    event: &SseEvent,
+    parse_data: impl Fn(&str) -> anyhow::Result<Option<Delta>>,
+) -> anyhow::Result<Option<Delta>> {
+    match event.event.as_str() {
+        "ping" => Ok(None),
+        _ => parse_data(&event.data),
+    }
+}
+
+/// The headers are passed on in a delta of their own
+fn parse_received(
+    received: Received,
+    parse_data: impl Fn(&str) -> anyhow::Result<Option<Delta>>,
+) -> anyhow::Result<Option<Delta>> {
+    match received {
+        Received::Headers(headers) => Ok(Some(Delta {
+            headers,
+            ..Default::default()
+        })),
+        Received::Event(event) => parse_event(&event, parse_data),
+    }
+}
+
+/// Parses the data of a chat completions SSE event
+fn parse_delta(data: &str) -> anyhow::Result<Option<Delta>> {
+    parse_event_data(data, parse_delta_value)
+}
+
+/// Like [`parse_delta`], but the content is read from `pointer` if it's there
+fn parse_delta_at(data: &str, pointer: &str) -> anyhow::Result<Option<Delta>> {
+    parse_event_data(data, |value| parse_delta_value_at(value, pointer))
+}
+
+/// Parses the data of a legacy completions SSE event
+fn parse_text_delta(data: &str) -> anyhow::Result<Option<Delta>> {
+    parse_event_data(data, parse_text_value)
+}
+
+/// `None` if it's the end of the stream or blank. Multiple JSON objects within the data are
+/// merged into one delta.
+fn parse_event_data(
+    data: &str,
+    parse_value: impl Fn(Value) -> anyhow::Result<Delta>,
+) -> anyhow::Result<Option<Delta>> {
+    let data = data.trim();
+
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(None);
+    }
+
+    let mut merged: Option<Delta> = None;
+
+    for value in serde_json::Deserializer::from_str(data).into_iter::<Value>() {
+        let value =
+            value.with_context(|| format!("Couldn't parse the event data as JSON:\n{data}"))?;
+
+        let delta = parse_value(value)?;
+
+        match &mut merged {
+            Some(merged) => merged.merge(delta),
+            None => merged = Some(delta),
+        }
+    }
+
+    // e.g. the chunk announcing the role, which is skipped like a keep-alive
+    Ok(merged.filter(|delta| *delta != Delta::default()))
+}
+
+fn parse_delta_value(value: Value) -> anyhow::Result<Delta> {
+    // Chunks with only the usage come without choices, there's nothing to paste from them
+    if value
+        .get("choices")
+        .and_then(Value::as_array)
+        .is_some_and(Vec::is_empty)
+    {
+        return Ok(Delta {
+            system_fingerprint: system_fingerprint(&value),
+            ..Delta::default()
+        });
+    }
+
+    // Non-streamed responses have the whole message instead
+    let delta = value
+        .pointer("/choices/0/delta")
+        .or_else(|| value.pointer("/choices/0/message"))
+        .filter(|delta| delta.is_object())
+        .ok_or_else(|| anyhow!("Delta not found within:\n{value:#}"))?;
+
+    // Either may be missing or null while the other is being streamed
+    let field = |name: &str| {
+        delta
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let content = field("content");
+    let reasoning = field("reasoning_content");
+
+    let tool_calls = delta
+        .get("tool_calls")
+        .and_then(Value::as_array)
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(index, call)| ToolCallDelta::parse(index, call))
+                .collect()
         })
+        .unwrap_or_default();
+
+    let logprobs = match value.pointer("/choices/0/logprobs/content") {
+        Some(logprobs) if !logprobs.is_null() => {
+            serde_json::from_value::<Vec<TokenLogprob>>(logprobs.clone())?
+        }
+        _ => vec![],
+    };
+
+    Ok(Delta {
+        content,
+        reasoning,
+        finish_reason: finish_reason(&value),
+        logprobs,
+        system_fingerprint: system_fingerprint(&value),
+        headers: vec![],
+        tool_calls,
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::openai::{CompletionRequest, Message, Role};
-    use iced::futures::TryStreamExt;
+/// Where the content of a chat completions delta is, unless a provider puts it elsewhere
+pub const DEFAULT_DELTA_POINTER: &str = "/choices/0/delta/content";
 
-    #[tokio::test]
-    async fn together() {
-        let api_key = std::env::var("TOGETHER_API_KEY").unwrap();
+/// Blank or a JSON pointer, which starts with a slash
+pub fn valid_json_pointer(pointer: &str) -> bool {
+    pointer.is_empty() || pointer.starts_with('/')
+}
 
-        println!("Using api key: {api_key}");
+/// Falls back to the standard delta if there's no string at `pointer`. The rest of the delta is
+/// optional alongside a custom pointer.
+fn parse_delta_value_at(value: Value, pointer: &str) -> anyhow::Result<Delta> {
+    if pointer.is_empty() || pointer == DEFAULT_DELTA_POINTER {
+        return parse_delta_value(value);
+    }
 
-        let req = CompletionRequest::new(
-            vec![Message {
-                content: "hi".to_string(),
-                role: Role::User,
-            }],
-            "meta-llama/Meta-Llama-3.1-70B-Instruct-Turbo".to_string(),
-            1000,
-            0.0,
-        );
+    match value.pointer(pointer).and_then(Value::as_str) {
+        Some(content) => {
+            let content = content.to_string();
 
-        super::completions("https://api.together.xyz/", api_key.as_str(), req)
-            .try_for_each(|delta| async move {
-                println!("{delta}");
-                Ok(())
+            Ok(Delta {
+                content,
+                ..parse_delta_value(value).unwrap_or_default()
             })
-            .await
-            .unwrap();
+        }
+        None => parse_delta_value(value)
+            .with_context(|| format!("Nothing at the delta pointer {pointer} either")),
+    }
+}
+
+fn finish_reason(value: &Value) -> Option<String> {
+    value
+        .pointer("/choices/0/finish_reason")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn system_fingerprint(value: &Value) -> Option<String> {
+    value
+        .get("system_fingerprint")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn parse_text_value(value: Value) -> anyhow::Result<Delta> {
+    let text = value
+        .pointer("/choices/0/text")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Text not found within:\n{value:#}"))?;
+
+    Ok(Delta {
+        finish_reason: finish_reason(&value),
+        system_fingerprint: system_fingerprint(&value),
+        ..Delta::content(text)
+    })
+}
+
+/// Settings that the HTTP client is built from. With both proxies blank reqwest's own system
+/// proxy detection is left to handle `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, setting either
+/// replaces it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientOptions {
+    pub http_proxy: String,
+    pub https_proxy: String,
+    pub danger_accept_invalid_certs: bool,
+    /// Path to a PEM file with an extra root certificate, blank for none
+    pub ca_certificate_path: String,
+    /// Blank doesn't send one
+    pub user_agent: String,
+}
+
+impl ClientOptions {
+    async fn build(&self) -> anyhow::Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(self.danger_accept_invalid_certs);
+
+        if !self.http_proxy.is_empty() {
+            builder = builder.proxy(reqwest::Proxy::http(self.http_proxy.as_str())?);
+        }
+
+        if !self.https_proxy.is_empty() {
+            builder = builder.proxy(reqwest::Proxy::https(self.https_proxy.as_str())?);
+        }
+
+        if !self.user_agent.is_empty() {
+            builder = builder.user_agent(self.user_agent.as_str());
+        }
+
+        if !self.ca_certificate_path.is_empty() {
+            let path = self.ca_certificate_path.as_str();
+
+            let pem = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Couldn't read the CA certificate at {path}"))?;
+
+            builder = builder.add_root_certificate(
+                reqwest::Certificate::from_pem(pem.as_slice())
+                    .with_context(|| format!("{path} isn't a valid PEM certificate"))?,
+            );
+        }
+
+        builder.build().map_err(Into::into)
+    }
+}
+
+/// Whether the user agent can be sent as a header value
+pub fn valid_user_agent(user_agent: &str) -> bool {
+    HeaderValue::from_str(user_agent).is_ok()
+}
+
+/// Whether the proxy is either blank or parses as a proxy url
+pub fn valid_proxy(proxy: &str) -> bool {
+    proxy.is_empty() || reqwest::Proxy::all(proxy).is_ok()
+}
+
+/// Owns the HTTP client, rebuilding it only when the options it was built from change
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+    built: Option<(ClientOptions, Result<reqwest::Client, String>)>,
+}
+
+/// A client with the options it was built from, or why it couldn't be built
+#[derive(Debug, Clone)]
+pub struct BuiltClient(ClientOptions, Result<reqwest::Client, String>);
+
+impl BuiltClient {
+    /// Builds the client off the update loop, reading the CA certificate is file IO
+    pub async fn build(options: ClientOptions) -> Self {
+        let client = options.build().await.map_err(|err| format!("{err:#}"));
+
+        Self(options, client)
+    }
+
+    pub fn options(&self) -> &ClientOptions {
+        &self.0
+    }
+}
+
+impl HttpClient {
+    /// Whether the client wasn't built from the options
+    pub fn outdated(&self, options: &ClientOptions) -> bool {
+        !self
+            .built
+            .as_ref()
+            .is_some_and(|(built_options, _)| built_options == options)
+    }
+
+    pub fn set(&mut self, BuiltClient(options, client): BuiltClient) {
+        self.built = Some((options, client));
+    }
+
+    /// Returns whether the client was (re)built
+    pub async fn refresh(&mut self, options: &ClientOptions) -> bool {
+        if !self.outdated(options) {
+            return false;
+        }
+
+        self.set(BuiltClient::build(options.clone()).await);
+
+        true
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match &self.built {
+            Some((_, Err(err))) => Some(err.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self) -> anyhow::Result<reqwest::Client> {
+        match &self.built {
+            Some((_, Ok(client))) => Ok(client.clone()),
+            Some((_, Err(err))) => Err(anyhow!("Couldn't build the HTTP client: {err}")),
+            None => Err(anyhow!("The HTTP client hasn't been built yet")),
+        }
+    }
+}
+
+/// Appends each request body and the raw response chunks (as received, including partial frames)
+/// to a file with timestamps, for debugging provider quirks.
+///
+/// The log has the full prompts and responses along with anything else sent, so don't share it.
+#[derive(Debug, Clone)]
+pub struct RawLog {
+    path: PathBuf,
+    /// Past this the log is moved to `<path>.old`, replacing the previous one
+    max_bytes: u64,
+}
+
+impl RawLog {
+    /// `None` if the path is blank
+    pub fn new(path: &str) -> Option<Self> {
+        let path = path.trim();
+
+        (!path.is_empty()).then(|| Self {
+            path: PathBuf::from(path),
+            max_bytes: 5 * 1024 * 1024,
+        })
+    }
+
+    async fn append(&self, kind: &str, data: &str) -> anyhow::Result<()> {
+        let size = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+
+        if size > self.max_bytes {
+            let mut old = self.path.clone().into_os_string();
+            old.push(".old");
+
+            tokio::fs::rename(&self.path, old).await?;
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?
+            .write_all(format!("[{timestamp:.3}] {kind}: {data:?}\n").as_bytes())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Logging is best effort, it should never fail a request
+    async fn log(log: &Option<RawLog>, kind: &str, data: &str) {
+        if let Some(log) = log {
+            let _ = log.append(kind, data).await;
+        }
+    }
+}
+
+fn endpoint_url(base_url: &str, path: &str) -> String {
+    match base_url.chars().last() {
+        Some('/') => format!("{base_url}{path}"),
+        _ => format!("{base_url}/{path}"),
+    }
+}
+
+/// Where requests go, for deployments that route by the model in the path rather than the body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routing {
+    /// Replaces the endpoint's path if not blank, with `{model}` filled in with the model
+    pub path_template: String,
+    /// Whether the model is still sent in the body when the path template has it
+    pub model_in_body: bool,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Self {
+            path_template: "".to_string(),
+            model_in_body: true,
+        }
+    }
+}
+
+/// Percent-encodes all but the unreserved characters so `text` stays one path segment
+fn path_segment(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+impl Routing {
+    fn url(&self, base_url: &str, default_path: &str, model: &str) -> String {
+        match self.path_template.trim().trim_start_matches('/') {
+            "" => endpoint_url(base_url, default_path),
+            template => endpoint_url(base_url, &template.replace("{model}", &path_segment(model))),
+        }
+    }
+
+    fn body(&self, request: &impl Serialize) -> String {
+        if self.model_in_body || !self.path_template.contains("{model}") {
+            return json_body(request);
+        }
+
+        let mut body = serde_json::to_value(request).unwrap(); // Impossible, like json_body
+
+        if let Value::Object(fields) = &mut body {
+            fields.remove("model");
+        }
+
+        body.to_string()
+    }
+}
+
+/// Returns a completions stream with the completion delta as each item, the content of each
+/// is read from `delta_pointer` if it's there. Failing to connect is retried up to
+/// `connect_retries` times.
+#[allow(clippy::too_many_arguments)]
+pub fn completions(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+    request: CompletionRequest,
+    routing: &Routing,
+    connect_retries: u32,
+    delta_pointer: String,
+    log: Option<RawLog>,
+) -> impl Stream<Item = anyhow::Result<Delta>> {
+    let url = routing.url(base_url, "v1/chat/completions", &request.model);
+    let body = routing.body(&request);
+
+    events(client.post(url), body, api_key, connect_retries, log).try_filter_map(move |received| {
+        future::ready(parse_received(received, |data| {
+            parse_delta_at(data, &delta_pointer)
+        }))
+    })
+}
+
+/// Parallel to [`completions`] for the legacy endpoint that base models are served from
+pub fn text_completions(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+    request: TextCompletionRequest,
+    routing: &Routing,
+    connect_retries: u32,
+    log: Option<RawLog>,
+) -> impl Stream<Item = anyhow::Result<Delta>> {
+    let url = routing.url(base_url, "v1/completions", &request.model);
+    let body = routing.body(&request);
+
+    events(client.post(url), body, api_key, connect_retries, log)
+        .try_filter_map(|received| future::ready(parse_received(received, parse_text_delta)))
+}
+
+/// Prefixed with `file:`, the key is read from the rest as a path at request time so it never
+/// has to be in the settings
+async fn resolve_api_key(api_key: &str) -> anyhow::Result<String> {
+    let Some(path) = api_key.trim().strip_prefix("file:").map(str::trim) else {
+        return Ok(api_key.to_string());
+    };
+
+    let key = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Couldn't read the API key from {path}"))?;
+
+    Ok(key.trim().to_string())
+}
+
+/// Web pages rather than an API, usually from a base URL missing its API path
+fn is_html(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|content_type| {
+        content_type
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("text/html")
+    })
+}
+
+/// Whether the response is a single JSON body rather than events, going by its first
+/// non-whitespace byte since servers don't always label either right. Events always start with a
+/// field name or a comment.
+fn is_json_body(head: &[u8]) -> bool {
+    head.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Serialized up front so the exact body can be logged
+fn json_body(request: &impl Serialize) -> String {
+    serde_json::to_string(request).unwrap() // Impossible, the requests are plain structs
+}
+
+/// Before the first retry, each later one waits this much longer than the last
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Sends the request, retrying only failures to establish the connection. Nothing reached the
+/// server by then, so unlike retrying a failed response it can't start a second generation.
+async fn connect(
+    request: reqwest::RequestBuilder,
+    body: String,
+    retries: u32,
+) -> reqwest::Result<reqwest::Response> {
+    let mut attempt = 0;
+
+    loop {
+        // Only requests with streamed bodies can't be cloned, ours is set on each attempt
+        let Some(retry) = request.try_clone().filter(|_| attempt < retries) else {
+            return request.body(body).send().await;
+        };
+
+        match retry.body(body.clone()).send().await {
+            Err(err) if err.is_connect() => {
+                attempt += 1;
+                tokio::time::sleep(CONNECT_RETRY_DELAY * attempt).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Fails with what went wrong if the response isn't a successful API response
+async fn check_response(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+
+    if is_html(content_type) {
+        return Err(anyhow!(
+            "Expected a JSON or SSE API but got an HTML page ({status}), check the base URL"
+        ));
+    }
+
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+
+        return Err(anyhow!("Request failed with {status}:\n{body}"));
+    }
+
+    Ok(response)
+}
+
+/// Sends the request and streams back the headers then the server-sent events of the response,
+/// the headers come first even if the request failed
+fn events(
+    request: reqwest::RequestBuilder,
+    body: String,
+    api_key: &str,
+    connect_retries: u32,
+    log: Option<RawLog>,
+) -> impl Stream<Item = anyhow::Result<Received>> {
+    let request = request.header(CONTENT_TYPE, "application/json");
+    let api_key = api_key.to_string();
+
+    let events = async move {
+        let api_key = resolve_api_key(&api_key).await?;
+        let request = request.header(AUTHORIZATION, format!("Bearer {api_key}"));
+
+        RawLog::log(&log, "request", &body).await;
+
+        let response = connect(request, body, connect_retries).await?;
+
+        // Sent even for failed requests, their rate limit headers are what's worth seeing
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let headers = stream::once(future::ready(Ok(Received::Headers(headers))));
+
+        let response = match check_response(response).await {
+            Ok(response) => response,
+            Err(err) => return Ok(headers.chain(stream::once(future::ready(Err(err)))).boxed()),
+        };
+
+        let mut chunks = response
+            .bytes_stream()
+            .map_err(anyhow::Error::from)
+            .and_then(move |bytes| {
+                let log = log.clone();
+
+                async move {
+                    RawLog::log(&log, "chunk", &String::from_utf8_lossy(&bytes)).await;
+
+                    Ok(bytes)
+                }
+            })
+            .boxed();
+
+        // Enough of the body to tell what it is
+        let mut head = vec![];
+
+        while head.iter().all(u8::is_ascii_whitespace) {
+            match chunks.next().await {
+                Some(chunk) => head.extend_from_slice(&chunk?),
+                None => break,
+            }
+        }
+
+        if is_json_body(&head) {
+            let mut body = head;
+
+            while let Some(chunk) = chunks.next().await {
+                body.extend_from_slice(&chunk?);
+            }
+
+            let event = SseEvent {
+                event: "message".to_string(),
+                data: String::from_utf8_lossy(&body).into_owned(),
+            };
+
+            return Ok(headers
+                .chain(stream::once(future::ready(Ok(Received::Event(event)))))
+                .boxed());
+        }
+
+        let events = stream::once(future::ready(Ok(head.into())))
+            .chain(chunks)
+            .scan(SseParser::default(), |parser, chunk| {
+                let events = chunk.map(|bytes| parser.feed_bytes(&bytes));
+
+                future::ready(Some(events))
+            })
+            .map_ok(|events| stream::iter(events.into_iter().map(Received::Event).map(Ok)))
+            .try_flatten();
+
+        Ok(headers.chain(events).boxed())
+    };
+
+    stream::once(events).try_flatten()
+}
+
+/// Streams back an echo of the last message word by word with a delay between each,
+/// parallel to [`completions`] for trying out the UI without making requests
+pub fn mock_completions(
+    request: CompletionRequest,
+    delay: Duration,
+) -> impl Stream<Item = anyhow::Result<Delta>> {
+    let last_message = request
+        .messages
+        .last()
+        .map(|msg| msg.content.as_str())
+        .unwrap_or_default();
+
+    let mut deltas = format!("This is a mock response to: {last_message}")
+        .split_inclusive(' ')
+        .map(Delta::content)
+        .collect::<Vec<_>>();
+
+    // Like the last chunk of a real stream
+    if let Some(last) = deltas.last_mut() {
+        last.finish_reason = Some("stop".to_string());
+    }
+
+    stream::iter(deltas).then(move |delta| async move {
+        tokio::time::sleep(delay).await;
+
+        Ok(delta)
+    })
+}
+
+pub type DeltaStream = BoxStream<'static, anyhow::Result<Delta>>;
+
+/// Coalesces the deltas received within `interval` of the first one into a single delta, so
+/// fast providers cause fewer UI updates. Errors are passed through after the pending batch.
+pub fn batch_deltas(
+    deltas: DeltaStream,
+    interval: Duration,
+) -> impl Stream<Item = anyhow::Result<Delta>> {
+    stream::unfold(
+        (Some(deltas), None),
+        move |(deltas, pending): (Option<DeltaStream>, Option<anyhow::Error>)| async move {
+            if let Some(err) = pending {
+                return Some((Err(err), (deltas, None)));
+            }
+
+            let mut deltas = deltas?;
+
+            let mut batch = match deltas.next().await? {
+                Ok(delta) => delta,
+                Err(err) => return Some((Err(err), (Some(deltas), None))),
+            };
+
+            let deadline = tokio::time::Instant::now() + interval;
+
+            loop {
+                match tokio::time::timeout_at(deadline, deltas.next()).await {
+                    Ok(Some(Ok(delta))) => batch.merge(delta),
+                    Ok(Some(Err(err))) => return Some((Ok(batch), (Some(deltas), Some(err)))),
+                    Ok(None) => return Some((Ok(batch), (None, None))),
+                    Err(_) => return Some((Ok(batch), (Some(deltas), None))),
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::openai::{
+        ClientOptions, CompletionRequest, Delta, HttpClient, MaxTokensField, Message, RawLog, Role,
+    };
+    use iced::futures::TryStreamExt;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn api_keys_from_files() {
+        use crate::openai::resolve_api_key;
+
+        let path = std::env::temp_dir().join(format!("playground-key-{}", std::process::id()));
+        let reference = format!("file:{}", path.display());
+        tokio::fs::write(&path, "sk-secret\n").await.unwrap();
+
+        assert_eq!(resolve_api_key(&reference).await.unwrap(), "sk-secret");
+        assert_eq!(resolve_api_key("sk-inline").await.unwrap(), "sk-inline");
+
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert!(resolve_api_key(&reference).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_retries() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Instant;
+
+        use crate::openai::{connect, CONNECT_RETRY_DELAY};
+
+        let client = reqwest::Client::new();
+
+        // Nothing listens on the port once the listener is dropped
+        let refused = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let start = Instant::now();
+
+        let err = connect(
+            client.post(format!("http://{refused}")),
+            "{}".to_string(),
+            2,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.is_connect());
+        assert!(start.elapsed() >= CONNECT_RETRY_DELAY * 3);
+
+        // A failed response isn't retried, the server only accepts once
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]).unwrap();
+
+            stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+        });
+
+        let response = connect(client.post(format!("http://{addr}")), "{}".to_string(), 2)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 500);
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn batch_deltas() {
+        use iced::futures::{stream, StreamExt};
+
+        let burst = || stream::iter((0..5).map(|i| Ok(Delta::content(i.to_string()))));
+
+        let deltas = burst()
+            .chain(stream::once(async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(Delta::content("-"))
+            }))
+            .chain(burst())
+            .chain(stream::once(async { Err(anyhow::anyhow!("failed")) }))
+            .boxed();
+
+        let batched = super::batch_deltas(deltas, Duration::from_millis(30))
+            .collect::<Vec<_>>()
+            .await;
+
+        // 12 deltas become 2 batches and the error
+        assert_eq!(batched.len(), 3);
+        assert_eq!(batched[0].as_ref().unwrap().content, "01234");
+        assert_eq!(batched[1].as_ref().unwrap().content, "-01234");
+        assert!(batched[2].is_err());
+    }
+
+    #[tokio::test]
+    async fn mock_completions() {
+        let req = CompletionRequest::new(
+            vec![Message {
+                content: "hello there".to_string(),
+                role: Role::User,
+                tool_call_id: None,
+                tool_calls: vec![],
+            }],
+            "mock".to_string(),
+            1000,
+            MaxTokensField::MaxTokens,
+            1.0,
+        );
+
+        let deltas = super::mock_completions(req, Duration::ZERO)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        // Ends like a real stream
+        assert_eq!(
+            deltas
+                .last()
+                .and_then(|delta| delta.finish_reason.as_deref()),
+            Some("stop")
+        );
+
+        let deltas = deltas
+            .into_iter()
+            .map(|delta| delta.content)
+            .collect::<Vec<_>>();
+
+        assert_eq!(deltas.first().map(String::as_str), Some("This "));
+        assert_eq!(deltas.concat(), "This is a mock response to: hello there");
+    }
+
+    #[tokio::test]
+    async fn proxies() {
+        assert!(super::valid_proxy(""));
+        assert!(super::valid_proxy("http://localhost:8080"));
+        assert!(!super::valid_proxy("http://[::1"));
+
+        let options = ClientOptions {
+            http_proxy: "http://[::1".to_string(),
+            https_proxy: "".to_string(),
+            danger_accept_invalid_certs: false,
+            ca_certificate_path: "".to_string(),
+            user_agent: "".to_string(),
+        };
+
+        let mut client = HttpClient::default();
+
+        assert!(client.refresh(&options).await);
+        assert!(client.error().is_some());
+        assert!(client.get().is_err());
+    }
+
+    #[tokio::test]
+    async fn user_agent() {
+        assert!(super::valid_user_agent("playground/0.1.0"));
+        assert!(!super::valid_user_agent("line\nbreak"));
+
+        let mut client = HttpClient::default();
+        client
+            .refresh(&ClientOptions {
+                user_agent: "playground/0.1.0".to_string(),
+                ..Default::default()
+            })
+            .await;
+        assert!(client.get().is_ok());
+
+        client
+            .refresh(&ClientOptions {
+                user_agent: "line\nbreak".to_string(),
+                ..Default::default()
+            })
+            .await;
+        assert!(client.get().is_err());
+    }
+
+    #[tokio::test]
+    async fn client_rebuilds_on_change() {
+        let mut client = HttpClient::default();
+        let mut options = ClientOptions::default();
+
+        assert!(client.get().is_err());
+        assert!(client.refresh(&options).await);
+        assert!(!client.refresh(&options).await);
+        assert!(client.get().is_ok());
+
+        options.danger_accept_invalid_certs = true;
+
+        assert!(client.refresh(&options).await);
+        assert!(!client.refresh(&options).await);
+    }
+
+    #[tokio::test]
+    async fn missing_ca_certificate() {
+        let mut client = HttpClient::default();
+
+        client
+            .refresh(&ClientOptions {
+                ca_certificate_path: "does/not/exist.pem".to_string(),
+                ..Default::default()
+            })
+            .await;
+
+        assert!(client
+            .error()
+            .is_some_and(|err| err.contains("does/not/exist.pem")));
+    }
+
+    #[test]
+    fn delta_pointer() {
+        let data = r#"{"output":{"text":"Hi"},"choices":[{"finish_reason":"stop"}]}"#;
+
+        let delta = super::parse_delta_at(data, "/output/text")
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.content, "Hi");
+        assert_eq!(delta.finish_reason.as_deref(), Some("stop"));
+
+        // Standard deltas still parse when the pointer misses
+        let standard = r#"{"choices":[{"delta":{"content":"Hi"}}]}"#;
+        assert_eq!(
+            super::parse_delta_at(standard, "/output/text")
+                .unwrap()
+                .unwrap()
+                .content,
+            "Hi"
+        );
+
+        let err = super::parse_delta_at(r#"{"output":{}}"#, "/output/text").unwrap_err();
+        assert!(format!("{err:#}").contains("/output/text"));
+
+        assert!(super::valid_json_pointer(""));
+        assert!(super::valid_json_pointer("/a/0"));
+        assert!(!super::valid_json_pointer("a/0"));
+    }
+
+    #[test]
+    fn parse_delta() {
+        assert_eq!(super::parse_delta("[DONE]").unwrap(), None);
+
+        assert_eq!(
+            super::parse_delta(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#).unwrap(),
+            Some(Delta::content("Hi"))
+        );
+
+        let delta = super::parse_delta(
+            r#"{"choices":[{"delta":{"content":"Hi"},"logprobs":{"content":[
+                {"token":"Hi","logprob":-0.1,"bytes":[72,105],"top_logprobs":[
+                    {"token":"Hi","logprob":-0.1,"bytes":[72,105]},
+                    {"token":"Hello","logprob":-2.5,"bytes":null}
+                ]}
+            ]}}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(delta.logprobs.len(), 1);
+        assert_eq!(delta.logprobs[0].token, "Hi");
+        assert_eq!(delta.logprobs[0].top_logprobs[1].token, "Hello");
+
+        // Skipped rather than failing the stream
+        assert_eq!(super::parse_delta(r#"{"choices":[]}"#).unwrap(), None);
+        assert_eq!(
+            super::parse_delta(r#"{"choices":[{"delta":{}}]}"#).unwrap(),
+            None
+        );
+        assert_eq!(
+            super::parse_delta(r#"{"choices":[{"delta":{"role":"assistant","content":null}}]}"#)
+                .unwrap(),
+            None
+        );
+
+        // Still errors when the chunk isn't a completion chunk at all
+        assert!(super::parse_delta(r#"{"choices":[{}]}"#).is_err());
+        assert!(super::parse_delta(r#"{"error":"overloaded"}"#).is_err());
+    }
+
+    #[test]
+    fn tool_calls() {
+        use crate::openai::{append_tool_calls, ToolCall};
+
+        let chunks = [
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function",
+                "function":{"name":"weather","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\""}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":":\"Oslo\"}"}},
+                {"index":1,"id":"call_2","function":{"name":"time","arguments":"{}"}}]}}]}"#,
+        ];
+
+        let mut calls: Vec<ToolCall> = vec![];
+
+        for chunk in chunks {
+            let delta = super::parse_delta(chunk).unwrap().unwrap();
+            append_tool_calls(&mut calls, delta.tool_calls);
+        }
+
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.name, "weather");
+        assert_eq!(calls[0].function.arguments, r#"{"city":"Oslo"}"#);
+        assert_eq!(calls[1].id, "call_2");
+
+        // Sent back the way the API expects them
+        let message = Message {
+            content: String::new(),
+            role: Role::Assistant,
+            tool_call_id: None,
+            tool_calls: calls,
+        };
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            value.pointer("/tool_calls/0/type"),
+            Some(&Value::from("function"))
+        );
+        assert_eq!(
+            value.pointer("/tool_calls/1/function/name"),
+            Some(&Value::from("time"))
+        );
+    }
+
+    #[test]
+    fn finish_reason() {
+        let delta = super::parse_delta(
+            r#"{"choices":[{"delta":{"content":""},"finish_reason":"content_filter"}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(delta.finish_reason.as_deref(), Some("content_filter"));
+
+        let mut merged = delta.clone();
+        merged.merge(Delta::content("later"));
+        assert_eq!(merged.finish_reason.as_deref(), Some("content_filter"));
+    }
+
+    #[test]
+    fn system_fingerprint() {
+        let delta = super::parse_delta(
+            r#"{"system_fingerprint":"fp_1","choices":[{"delta":{"content":"Hi"}}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(delta.system_fingerprint.as_deref(), Some("fp_1"));
+
+        // Kept through the usage chunk, which has nothing else
+        let delta = super::parse_delta(r#"{"system_fingerprint":"fp_2","choices":[]}"#)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(delta.system_fingerprint.as_deref(), Some("fp_2"));
+
+        let mut merged = Delta {
+            system_fingerprint: Some("fp_1".to_string()),
+            ..Delta::default()
+        };
+        merged.merge(Delta::content("later"));
+        assert_eq!(merged.system_fingerprint.as_deref(), Some("fp_1"));
+    }
+
+    #[test]
+    fn headers_delta() {
+        let headers = vec![("x-request-id".to_string(), "req_1".to_string())];
+
+        let delta = super::parse_received(
+            super::Received::Headers(headers.clone()),
+            super::parse_delta,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(delta.headers, headers);
+
+        // Kept when batched with the deltas after it
+        let mut merged = delta;
+        merged.merge(Delta::content("Hi"));
+        assert_eq!(merged.headers, headers);
+        assert_eq!(merged.content, "Hi");
+    }
+
+    #[test]
+    fn reasoning_delta() {
+        let delta = super::parse_delta(
+            r#"{"choices":[{"delta":{"content":null,"reasoning_content":"Hmm"}}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(delta.content, "");
+        assert_eq!(delta.reasoning, "Hmm");
+
+        let delta = super::parse_delta(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(delta.reasoning, "");
+    }
+
+    #[test]
+    fn sse_parser() {
+        let raw = ": keep-alive\n\n\
+            data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\r\n\r\n\
+            event: ping\ndata: {}\n\n\
+            data:\n\n\
+            data:   \n\n\
+            : ping\n\
+            data: {\"choices\":[{\"delta\":{\"content\":\" a\"}}]}\n\
+            data: {\"choices\":[{\"delta\":{\"content\":\"b\"}}]}\n\n\
+            data: [DONE]\n\n";
+
+        // Split the raw stream into chunks of every size to cover frames split across chunks
+        for size in 1..raw.len() {
+            let mut parser = super::SseParser::default();
+
+            let deltas = raw
+                .as_bytes()
+                .chunks(size)
+                .flat_map(|chunk| parser.feed(std::str::from_utf8(chunk).unwrap()))
+                .filter_map(|event| super::parse_event(&event, super::parse_delta).unwrap())
+                .map(|delta| delta.content)
+                .collect::<Vec<_>>();
+
+            assert_eq!(deltas, ["Hi", " ab"]);
+        }
+    }
+
+    #[test]
+    fn split_utf8() {
+        let raw = "data: {\"choices\":[{\"delta\":{\"content\":\"héllo 👋\"}}]}\n\n".as_bytes();
+
+        // Splits inside the two and four byte characters
+        for at in 1..raw.len() {
+            let mut parser = super::SseParser::default();
+
+            let mut events = parser.feed_bytes(&raw[..at]);
+            events.extend(parser.feed_bytes(&raw[at..]));
+
+            let delta = super::parse_event(&events[0], super::parse_delta).unwrap();
+            assert_eq!(delta.unwrap().content, "héllo 👋");
+        }
+
+        let mut buffer = super::Utf8Buffer::default();
+        assert_eq!(buffer.push(b"a\xffb\xe2\x82"), "a\u{FFFD}b");
+        assert_eq!(buffer.push(b"\xac"), "€");
+    }
+
+    #[test]
+    fn mislabeled_bodies() {
+        // A single JSON body sent as text/event-stream, e.g. by servers ignoring `stream`
+        let json =
+            b"\n {\"choices\":[{\"message\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+        assert!(super::is_json_body(json));
+
+        let event = super::SseEvent {
+            event: "message".to_string(),
+            data: String::from_utf8_lossy(json).into_owned(),
+        };
+        let delta = super::parse_event(&event, super::parse_delta)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.content, "Hi");
+        assert_eq!(delta.finish_reason.as_deref(), Some("stop"));
+
+        // An event stream sent as application/json
+        assert!(!super::is_json_body(b"data: {\"choices\":[]}\n\n"));
+        assert!(!super::is_json_body(b": keep-alive\n"));
+        assert!(!super::is_json_body(b""));
+    }
+
+    #[test]
+    fn text_completion() {
+        let req = CompletionRequest::new(
+            vec![
+                Message {
+                    content: "Once upon".to_string(),
+                    role: Role::User,
+                    tool_call_id: None,
+                    tool_calls: vec![],
+                },
+                Message {
+                    content: " a time".to_string(),
+                    role: Role::Assistant,
+                    tool_call_id: None,
+                    tool_calls: vec![],
+                },
+            ],
+            "model".to_string(),
+            10,
+            MaxTokensField::MaxCompletionTokens,
+            1.0,
+        );
+
+        let value = serde_json::to_value(req.into_text_completion()).unwrap();
+        assert_eq!(value["prompt"], "Once upon a time");
+        assert_eq!(value["max_tokens"], 10);
+        assert_eq!(value.get("messages"), None);
+
+        assert_eq!(
+            super::parse_text_delta(r#"{"choices":[{"text":" there","index":0}]}"#).unwrap(),
+            Some(Delta::content(" there"))
+        );
+        assert!(super::parse_text_delta(r#"{"choices":[{"delta":{}}]}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn raw_log() {
+        let name = format!("playground-raw-log-{}.log", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        let old = std::env::temp_dir().join(format!("{name}.old"));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&old);
+
+        assert!(RawLog::new("  ").is_none());
+
+        let log = RawLog {
+            path: path.clone(),
+            max_bytes: 100,
+        };
+
+        log.append("request", "{\"model\":\"m\"}").await.unwrap();
+        log.append("chunk", "data: {\"cho").await.unwrap();
+
+        let logged = std::fs::read_to_string(&path).unwrap();
+        assert!(logged.contains("request: \"{\\\"model\\\":\\\"m\\\"}\""));
+        assert!(logged.contains("chunk: \"data: {\\\"cho\""));
+
+        // Rotated once past the cap
+        log.append("chunk", &"x".repeat(100)).await.unwrap();
+        log.append("chunk", "after").await.unwrap();
+
+        assert!(old.exists());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("after"));
+    }
+
+    #[test]
+    fn json_schema_request() {
+        let req =
+            CompletionRequest::new(vec![], "m".to_string(), 1, MaxTokensField::MaxTokens, 1.0);
+
+        let json = serde_json::to_value(&req).unwrap();
+        assert!(json.get("response_format").is_none());
+
+        let json =
+            serde_json::to_value(req.with_json_schema(serde_json::json!({ "type": "object" })))
+                .unwrap();
+        assert_eq!(json["response_format"]["type"], "json_schema");
+        assert_eq!(
+            json["response_format"]["json_schema"]["schema"]["type"],
+            "object"
+        );
+    }
+
+    #[test]
+    fn stop_formats() {
+        use super::StopFormat;
+
+        let stop = |format| {
+            let req =
+                CompletionRequest::new(vec![], "m".to_string(), 10, MaxTokensField::MaxTokens, 0.0)
+                    .with_stop(vec!["\n\n".to_string(), "###".to_string()], format);
+
+            serde_json::to_value(req).unwrap()["stop"].clone()
+        };
+
+        assert_eq!(stop(StopFormat::Array), serde_json::json!(["\n\n", "###"]));
+        assert_eq!(stop(StopFormat::Joined), serde_json::json!("\n\n\n###"));
+
+        // Not sent without any
+        let req =
+            CompletionRequest::new(vec![], "m".to_string(), 10, MaxTokensField::MaxTokens, 0.0)
+                .with_stop(vec![], StopFormat::Joined);
+        assert!(serde_json::to_value(req).unwrap().get("stop").is_none());
+    }
+
+    #[test]
+    fn html_responses() {
+        assert!(super::is_html(Some("text/html; charset=utf-8")));
+        assert!(super::is_html(Some("Text/HTML")));
+        assert!(!super::is_html(Some("text/event-stream")));
+        assert!(!super::is_html(Some("application/json")));
+        assert!(!super::is_html(None));
+    }
+
+    #[test]
+    fn model_in_path() {
+        use super::Routing;
+
+        let req = CompletionRequest::new(
+            vec![],
+            "gpt-4o".to_string(),
+            10,
+            MaxTokensField::MaxTokens,
+            0.0,
+        );
+
+        let default = Routing::default();
+        assert_eq!(
+            default.url("https://example.com/", "v1/chat/completions", req.model()),
+            "https://example.com/v1/chat/completions"
+        );
+
+        let mut routing = Routing {
+            path_template: "/openai/deployments/{model}/chat/completions?api-version=1".to_string(),
+            model_in_body: true,
+        };
+        assert_eq!(
+            routing.url("https://example.com", "v1/chat/completions", req.model()),
+            "https://example.com/openai/deployments/gpt-4o/chat/completions?api-version=1"
+        );
+
+        assert_eq!(
+            routing.url("https://example.com", "", "org/model:v1 ä"),
+            "https://example.com/openai/deployments/org%2Fmodel%3Av1%20%C3%A4/chat/completions?api-version=1"
+        );
+
+        let sends_model = |routing: &Routing| {
+            serde_json::from_str::<Value>(&routing.body(&req)).unwrap()["model"] == "gpt-4o"
+        };
+        assert!(sends_model(&routing));
+
+        routing.model_in_body = false;
+        assert!(!sends_model(&routing));
+
+        // Has to go somewhere
+        routing.path_template = "v2/chat".to_string();
+        assert!(sends_model(&routing));
+    }
+
+    #[test]
+    fn malformed_event_data() {
+        let data = r#"{"choices":[{"delta":{"content":"Hi"#;
+
+        let err = super::parse_delta(data).unwrap_err();
+
+        assert!(format!("{err:#}").contains(data));
+    }
+
+    #[test]
+    fn logprobs_request() {
+        let req = CompletionRequest::new(
+            vec![],
+            "model".to_string(),
+            1,
+            MaxTokensField::MaxTokens,
+            1.0,
+        );
+
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value.get("logprobs"), None);
+        assert_eq!(value.get("top_logprobs"), None);
+
+        let value = serde_json::to_value(req.with_logprobs(3)).unwrap();
+        assert_eq!(value.get("logprobs"), Some(&Value::from(true)));
+        assert_eq!(value.get("top_logprobs"), Some(&Value::from(3)));
+    }
+
+    #[test]
+    fn user_field() {
+        let req = || {
+            CompletionRequest::new(
+                vec![],
+                "model".to_string(),
+                1,
+                MaxTokensField::MaxTokens,
+                1.0,
+            )
+        };
+
+        let value = serde_json::to_value(req().with_user("".to_string())).unwrap();
+        assert_eq!(value.get("user"), None);
+
+        let value = serde_json::to_value(req().with_user("user-1".to_string())).unwrap();
+        assert_eq!(value.get("user"), Some(&Value::from("user-1")));
+    }
+
+    #[test]
+    fn parse_messages() {
+        let (messages, warnings) = super::parse_messages(
+            r#"[
+                {"role": "system", "content": "Be brief"},
+                {"role": "narrator", "content": "Unknown"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What's in"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}},
+                    {"type": "text", "text": "this image?"}
+                ]},
+                {"role": "assistant", "content": "A cat"},
+                {"role": "assistant", "content": null, "tool_calls": [{"id": "call_1",
+                    "type": "function", "function": {"name": "check", "arguments": "{}"}}]},
+                {"role": "tool", "content": "{\"ok\": true}", "tool_call_id": "call_1"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Skipped message 1"));
+        assert_eq!(messages[3].tool_calls[0].function.name, "check");
+        assert_eq!(messages[4].tool_call_id.as_deref(), Some("call_1"));
+        assert!(!serde_json::to_string(&messages[0])
+            .unwrap()
+            .contains("tool_call_id"));
+
+        let messages = messages
+            .iter()
+            .map(|msg| (msg.role, msg.content.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                (Role::System, "Be brief"),
+                (Role::User, "What's in\nthis image?"),
+                (Role::Assistant, "A cat"),
+                (Role::Assistant, ""),
+                (Role::Tool, "{\"ok\": true}"),
+            ]
+        );
+
+        assert!(super::parse_messages(r#"{"role": "user"}"#).is_err());
+    }
+
+    #[test]
+    fn max_tokens_field() {
+        for (field, present, absent) in [
+            (
+                MaxTokensField::MaxTokens,
+                "max_tokens",
+                "max_completion_tokens",
+            ),
+            (
+                MaxTokensField::MaxCompletionTokens,
+                "max_completion_tokens",
+                "max_tokens",
+            ),
+        ] {
+            let req = CompletionRequest::new(vec![], "model".to_string(), 1000, field, 1.0);
+            let value = serde_json::to_value(&req).unwrap();
+
+            assert_eq!(value.get(present), Some(&Value::from(1000)));
+            assert_eq!(value.get(absent), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn together() {
+        let api_key = std::env::var("TOGETHER_API_KEY").unwrap();
+
+        println!("Using api key: {api_key}");
+
+        let req = CompletionRequest::new(
+            vec![Message {
+                content: "hi".to_string(),
+                role: Role::User,
+                tool_call_id: None,
+                tool_calls: vec![],
+            }],
+            "meta-llama/Meta-Llama-3.1-70B-Instruct-Turbo".to_string(),
+            1000,
+            MaxTokensField::MaxTokens,
+            0.0,
+        );
+
+        let mut client = HttpClient::default();
+        client.refresh(&Default::default()).await;
+        let client = client.get().unwrap();
+
+        super::completions(
+            &client,
+            "https://api.together.xyz/",
+            api_key.as_str(),
+            req,
+            &super::Routing::default(),
+            0,
+            super::DEFAULT_DELTA_POINTER.to_string(),
+            None,
+        )
+        .try_for_each(|delta| async move {
+            println!("{}", delta.content);
+            Ok(())
+        })
+        .await
+        .unwrap();
     }
 }
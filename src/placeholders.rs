@@ -0,0 +1,101 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Fills in `{{now}}` (UTC, RFC 3339), `{{date}}` and `{{env:VAR}}`. Anything else, including
+/// variables that aren't set, is left as it is.
+pub fn fill(text: &str, now: SystemTime, env: impl Fn(&str) -> Option<String>) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}").map(|end| end + 2) else {
+            break;
+        };
+
+        let placeholder = &rest[start..start + len];
+        let name = placeholder[2..len - 2].trim();
+
+        let value = match name {
+            "now" => Some(timestamp(now)),
+            "date" => Some(timestamp(now)[..10].to_string()),
+            _ => name.strip_prefix("env:").and_then(|var| env(var.trim())),
+        };
+
+        filled.push_str(&rest[..start]);
+        filled.push_str(value.as_deref().unwrap_or(placeholder));
+        rest = &rest[start + len..];
+    }
+
+    filled.push_str(rest);
+
+    filled
+}
+
+/// e.g. `2024-08-01T12:30:00Z`
+pub fn timestamp(now: SystemTime) -> String {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+
+    let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // The days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::placeholders::{fill, timestamp};
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            "2023-11-14T22:13:20Z"
+        );
+    }
+
+    #[test]
+    fn placeholders() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let env = |var: &str| (var == "NAME").then(|| "Ada".to_string());
+
+        assert_eq!(
+            fill("It's {{now}}, {{ date }}. Hi {{env:NAME}}!", now, env),
+            "It's 2023-11-14T22:13:20Z, 2023-11-14. Hi Ada!"
+        );
+
+        // Left alone when there's nothing to fill in
+        assert_eq!(
+            fill("{{env:UNSET}} {{other}} {{now", now, env),
+            "{{env:UNSET}} {{other}} {{now"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Transforms of the finished assistant message, picked in the settings
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcessor {
+    StripThinking,
+    UnescapeUnicode,
+    PrettyJson,
+}
+
+impl PostProcessor {
+    /// Also the order they're applied in, the JSON is only worth checking for once the thinking
+    /// is gone
+    pub const ALL: &'static [PostProcessor] = &[
+        PostProcessor::StripThinking,
+        PostProcessor::UnescapeUnicode,
+        PostProcessor::PrettyJson,
+    ];
+
+    fn apply(self, text: &str) -> String {
+        match self {
+            PostProcessor::StripThinking => strip_thinking(text),
+            PostProcessor::UnescapeUnicode => unescape_unicode(text),
+            PostProcessor::PrettyJson => pretty_json(text),
+        }
+    }
+}
+
+impl Display for PostProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PostProcessor::StripThinking => "Strip <think>…</think>",
+            PostProcessor::UnescapeUnicode => "Unescape \\uXXXX escapes",
+            PostProcessor::PrettyJson => "Pretty-print if JSON",
+        })
+    }
+}
+
+/// Applies the enabled post-processors in the order of [`PostProcessor::ALL`]
+pub fn apply(enabled: &[PostProcessor], text: &str) -> String {
+    PostProcessor::ALL
+        .iter()
+        .filter(|processor| enabled.contains(processor))
+        .fold(text.to_string(), |text, processor| processor.apply(&text))
+}
+
+/// Drops every closed `<think>` block along with the whitespace after it, an unclosed one is
+/// left alone since there's no telling where it was meant to end
+fn strip_thinking(text: &str) -> String {
+    const OPEN: &str = "<think>";
+    const CLOSE: &str = "</think>";
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(OPEN) {
+        let Some(end) = rest[start..]
+            .find(CLOSE)
+            .map(|end| start + end + CLOSE.len())
+        else {
+            break;
+        };
+
+        stripped.push_str(&rest[..start]);
+        rest = rest[end..].trim_start();
+    }
+
+    stripped.push_str(rest);
+
+    stripped
+}
+
+/// Turns `\uXXXX` escapes into the characters they stand for, including surrogate pairs.
+/// Anything that isn't a valid escape is left as it is.
+fn unescape_unicode(text: &str) -> String {
+    fn code_unit(text: &str) -> Option<u16> {
+        let hex = text.strip_prefix("\\u")?.get(..4)?;
+
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        u16::from_str_radix(hex, 16).ok()
+    }
+
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("\\u") {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(first) = code_unit(rest) else {
+            unescaped.push_str("\\u");
+            rest = &rest[2..];
+            continue;
+        };
+
+        let pair = code_unit(&rest[6..]).and_then(|second| {
+            char::decode_utf16([first, second])
+                .next()?
+                .ok()
+                .filter(|_| (0xD800..0xDC00).contains(&first))
+        });
+
+        match pair.or_else(|| char::from_u32(first as u32)) {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[if pair.is_some() { 12 } else { 6 }..];
+            }
+            // A lone surrogate
+            None => {
+                unescaped.push_str(&rest[..6]);
+                rest = &rest[6..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+/// Objects and arrays are pretty-printed, with their keys sorted. Anything else, including JSON
+/// scalars, is left as it is.
+fn pretty_json(text: &str) -> String {
+    match serde_json::from_str::<Value>(text.trim()) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => {
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| text.to_string())
+        }
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::postprocess::{apply, pretty_json, strip_thinking, unescape_unicode, PostProcessor};
+
+    #[test]
+    fn thinking() {
+        assert_eq!(
+            strip_thinking("<think>\nHmm.\n</think>\n\nHello <think>again</think> there"),
+            "Hello there"
+        );
+        assert_eq!(strip_thinking("<think>still going"), "<think>still going");
+        assert_eq!(strip_thinking("No thoughts"), "No thoughts");
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(unescape_unicode(r"caf\u00e9 \ud83d\ude00"), "café 😀");
+
+        // Left alone
+        assert_eq!(
+            unescape_unicode(r"\u12 \uzzzz \ud83d."),
+            r"\u12 \uzzzz \ud83d."
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            pretty_json(" {\"b\":[1],\"a\":null}\n"),
+            "{\n  \"a\": null,\n  \"b\": [\n    1\n  ]\n}"
+        );
+        assert_eq!(pretty_json("42"), "42");
+        assert_eq!(pretty_json("Not {json}"), "Not {json}");
+    }
+
+    #[test]
+    fn applied_in_order() {
+        let text = "<think>It's JSON</think>\n[\"\\u00e9\"]";
+
+        // Only valid JSON once the thinking is stripped
+        assert_eq!(
+            apply(
+                &[PostProcessor::PrettyJson, PostProcessor::StripThinking],
+                text
+            ),
+            "[\n  \"é\"\n]"
+        );
+        assert_eq!(apply(&[], text), text);
+    }
+}
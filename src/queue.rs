@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use iced::futures::{stream, Stream, StreamExt};
+use tokio::sync::Notify;
+
+/// Spaces out the requests of every tab and caps how many stream at once, so bursts of runs
+/// don't trip the provider's rate limits
+#[derive(Debug, Default)]
+pub struct RequestQueue {
+    state: Mutex<State>,
+    /// Notified whenever a request finishes
+    released: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    last_started: Option<Instant>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Limits {
+    /// Between the starts of consecutive requests
+    pub min_interval: Duration,
+    /// 0 for no limit
+    pub max_concurrent: usize,
+}
+
+/// Held for as long as the request streams
+pub struct Permit {
+    queue: Arc<RequestQueue>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.queue
+            .state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .running -= 1;
+
+        self.queue.released.notify_waiters();
+    }
+}
+
+impl RequestQueue {
+    /// Waits for a slot within the limits, in no particular order among the waiting requests
+    pub async fn acquire(self: Arc<Self>, limits: Limits) -> Permit {
+        loop {
+            // Created before checking so a release in between isn't missed
+            let released = self.released.notified();
+
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+
+                let full = limits.max_concurrent > 0 && state.running >= limits.max_concurrent;
+                let until_next = state.last_started.map_or(Duration::ZERO, |started| {
+                    limits.min_interval.saturating_sub(started.elapsed())
+                });
+
+                if !full && until_next.is_zero() {
+                    state.running += 1;
+                    state.last_started = Some(Instant::now());
+
+                    None
+                } else {
+                    Some((!full).then_some(until_next))
+                }
+            };
+
+            match wait {
+                None => {
+                    return Permit {
+                        queue: self.clone(),
+                    }
+                }
+                Some(None) => released.await,
+                Some(Some(until_next)) => tokio::time::sleep(until_next).await,
+            }
+        }
+    }
+
+    /// `requests` only starts once it gets a slot, which it holds until it ends or is dropped
+    pub fn throttle<S>(self: Arc<Self>, limits: Limits, requests: S) -> impl Stream<Item = S::Item>
+    where
+        S: Stream,
+    {
+        stream::once(async move { (self.acquire(limits).await, requests) }).flat_map(
+            |(permit, requests)| {
+                requests.map(move |item| {
+                    let _held = &permit;
+
+                    item
+                })
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::queue::{Limits, RequestQueue};
+
+    #[tokio::test]
+    async fn spaces_out_requests() {
+        let queue = Arc::new(RequestQueue::default());
+        let limits = Limits {
+            min_interval: Duration::from_millis(50),
+            max_concurrent: 0,
+        };
+
+        let start = Instant::now();
+
+        let first = queue.clone().acquire(limits).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // Without a concurrency limit, only the interval holds it back
+        let _second = queue.clone().acquire(limits).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        drop(first);
+    }
+
+    #[tokio::test]
+    async fn caps_concurrency() {
+        let queue = Arc::new(RequestQueue::default());
+        let limits = Limits {
+            min_interval: Duration::ZERO,
+            max_concurrent: 1,
+        };
+
+        let first = queue.clone().acquire(limits).await;
+
+        let waiting =
+            tokio::time::timeout(Duration::from_millis(20), queue.clone().acquire(limits)).await;
+        assert!(waiting.is_err());
+
+        drop(first);
+
+        let second =
+            tokio::time::timeout(Duration::from_millis(20), queue.clone().acquire(limits)).await;
+        assert!(second.is_ok());
+    }
+}
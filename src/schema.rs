@@ -0,0 +1,158 @@
+use anyhow::anyhow;
+use serde_json::Value;
+
+/// `None` if blank, otherwise the schema has to be a JSON object
+pub fn parse(text: &str) -> anyhow::Result<Option<Value>> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match serde_json::from_str(text)? {
+        schema @ Value::Object(_) => Ok(Some(schema)),
+        _ => Err(anyhow!("The schema isn't a JSON object")),
+    }
+}
+
+/// Checks a response against the schema it was requested with, describing the first few
+/// mismatches. Only `type`, `enum`, `properties`, `required`, `additionalProperties: false` and
+/// `items` are checked, the server enforces the rest.
+pub fn validate(schema: &Value, output: &str) -> Result<(), String> {
+    let value = serde_json::from_str::<Value>(output)
+        .map_err(|err| format!("The response isn't valid JSON: {err}"))?;
+
+    let mut mismatches = vec![];
+    check(schema, &value, "$", &mut mismatches);
+
+    match mismatches.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "The response doesn't match the schema:\n{}",
+            mismatches
+                .iter()
+                .take(5)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n")
+        )),
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, mismatches: &mut Vec<String>) {
+    let types = match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+
+    if !types.is_empty() && !types.iter().any(|ty| is_type(value, ty)) {
+        mismatches.push(format!(
+            "{path}: expected {}, got {}",
+            types.join(" or "),
+            type_name(value)
+        ));
+
+        return;
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            mismatches.push(format!("{path}: {value} isn't one of the allowed values"));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        mismatches.push(format!("{path}: missing the required \"{key}\""));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+
+            for (key, field) in object {
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(field_schema) => {
+                        check(field_schema, field, &format!("{path}.{key}"), mismatches)
+                    }
+                    None if closed => mismatches.push(format!("{path}: unexpected \"{key}\"")),
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{path}[{index}]"), mismatches);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        // Unknown types aren't ours to reject
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::schema::{parse, validate};
+
+    #[test]
+    fn parse_schema() {
+        assert!(parse("  ").unwrap().is_none());
+        assert!(parse("{\"type\": \"object\"}").unwrap().is_some());
+        assert!(parse("[1, 2]").is_err());
+        assert!(parse("{\"type\": ").is_err());
+    }
+
+    #[test]
+    fn validate_output() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] } }
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        });
+
+        assert!(validate(&schema, r#"{"name": "x", "age": 3, "tags": ["a"]}"#).is_ok());
+        assert!(validate(&schema, "not json").is_err());
+
+        let err = validate(&schema, r#"{"name": 1, "tags": ["c"], "extra": null}"#).unwrap_err();
+        assert!(err.contains("$.name: expected string, got number"));
+        assert!(err.contains("missing the required \"age\""));
+        assert!(err.contains("$.tags[0]: \"c\" isn't one of the allowed values"));
+        assert!(err.contains("unexpected \"extra\""));
+    }
+}
@@ -1,14 +1,24 @@
 use std::borrow::Cow;
 use std::fmt::Display;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use iced::{Border, Element, Length, Padding, Task, Theme};
+use iced::{time, Border, Element, Length, Padding, Subscription, Task, Theme};
 use iced::widget::{
-    button, column, Column, container, Container, row, slider, text, text_input,
-    TextInput,
+    button, checkbox, column, Column, container, Container, horizontal_space, pick_list, row,
+    scrollable, slider, text, text_input, TextInput,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::openai;
+use crate::openai::{
+    BuiltClient, ClientOptions, Endpoint, HttpClient, MaxTokensField, Provider, Routing, StopFormat,
+};
+use crate::postprocess::PostProcessor;
+use crate::queue::{Limits, RequestQueue};
+use crate::schema;
 use crate::PlaygroundMessage;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -62,16 +72,20 @@ impl<T: Default + Display> Default for Parsable<T> {
     }
 }
 
+pub fn invalid_text_input_style(theme: &Theme, status: text_input::Status) -> text_input::Style {
+    text_input::Style {
+        value: theme.palette().danger,
+        ..text_input::default(theme, status)
+    }
+}
+
 fn parsable_text_input<'a, T: FromStr>(
     placeholder: &'a str,
     parsable: &'a Parsable<T>,
     f: impl 'a + Fn(Parsable<T>) -> SettingsMessage,
 ) -> TextInput<'a, SettingsMessage> {
     let style_fn = match parsable.parsed {
-        None => |theme: &Theme, status| text_input::Style {
-            value: theme.palette().danger,
-            ..text_input::default(theme, status)
-        },
+        None => invalid_text_input_style,
         _ => text_input::default,
     };
 
@@ -80,6 +94,76 @@ fn parsable_text_input<'a, T: FromStr>(
         .on_input(move |changed| f(Parsable::parse(changed)))
 }
 
+fn valid_seed(seed: &str) -> bool {
+    seed.trim().is_empty() || seed.trim().parse::<i64>().is_ok()
+}
+
+/// Split on commas and trimmed, with `\n`, `\t`, `\,` and `\\` unescaped. Blank sequences are
+/// skipped.
+fn parse_stop_sequences(text: &str) -> Vec<String> {
+    let mut sequences = vec![];
+    let mut sequence = String::new();
+    // Past the last character that isn't unescaped whitespace, the rest is trimmed
+    let mut end = 0;
+    let mut chars = text.chars();
+
+    loop {
+        let c = match chars.next() {
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(escaped @ (',' | '\\')) => escaped,
+                // Not an escape, kept as it is
+                Some(other) => {
+                    sequence.push('\\');
+                    other
+                }
+                None => '\\',
+            },
+            Some(c) if c.is_whitespace() => {
+                if !sequence.is_empty() {
+                    sequence.push(c);
+                }
+
+                continue;
+            }
+            next @ (Some(',') | None) => {
+                sequence.truncate(end);
+                end = 0;
+
+                if !sequence.is_empty() {
+                    sequences.push(std::mem::take(&mut sequence));
+                }
+
+                match next {
+                    Some(_) => continue,
+                    None => return sequences,
+                }
+            }
+            Some(c) => c,
+        };
+
+        sequence.push(c);
+        end = sequence.len();
+    }
+}
+
+fn validated_text_input<'a>(
+    placeholder: &'a str,
+    value: &'a str,
+    valid: bool,
+    f: impl 'a + Fn(String) -> SettingsMessage,
+) -> TextInput<'a, SettingsMessage> {
+    let style_fn = match valid {
+        true => text_input::default,
+        false => invalid_text_input_style,
+    };
+
+    TextInput::new(placeholder, value)
+        .style(style_fn)
+        .on_input(f)
+}
+
 // rustrover can't resolve the column macro properly, so this is a stopgap
 fn pair_in_column<'a>(
     a: impl Into<Element<'a, SettingsMessage>>,
@@ -88,30 +172,470 @@ fn pair_in_column<'a>(
     column([a.into(), b.into()])
 }
 
+/// Generation params that can be switched to from the chat view
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub model: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    /// Seeds the system message when selected if not empty
+    pub system_prompt: String,
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// A short name typed in place of a full model id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelAlias {
+    pub alias: String,
+    pub model: String,
+}
+
+/// Providers whose defaults can be filled in, they're all OpenAI compatible
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum QuickSetup {
+    OpenAi,
+    Together,
+    Groq,
+    OpenRouter,
+    Ollama,
+    LlamaCpp,
+}
+
+impl QuickSetup {
+    pub const ALL: &'static [QuickSetup] = &[
+        QuickSetup::OpenAi,
+        QuickSetup::Together,
+        QuickSetup::Groq,
+        QuickSetup::OpenRouter,
+        QuickSetup::Ollama,
+        QuickSetup::LlamaCpp,
+    ];
+
+    /// Without the `v1/`, which is added for each endpoint
+    fn base_url(self) -> &'static str {
+        match self {
+            QuickSetup::OpenAi => "https://api.openai.com/",
+            QuickSetup::Together => "https://api.together.xyz/",
+            QuickSetup::Groq => "https://api.groq.com/openai/",
+            QuickSetup::OpenRouter => "https://openrouter.ai/api/",
+            QuickSetup::Ollama => "http://localhost:11434/",
+            QuickSetup::LlamaCpp => "http://localhost:8080/",
+        }
+    }
+
+    /// Of the provider's API keys, `None` if they don't share one. Only a hint, key formats
+    /// change and gateways issue their own.
+    fn key_prefix(self) -> Option<&'static str> {
+        match self {
+            QuickSetup::OpenAi => Some("sk-"),
+            QuickSetup::Groq => Some("gsk_"),
+            QuickSetup::OpenRouter => Some("sk-or-"),
+            QuickSetup::Together | QuickSetup::Ollama | QuickSetup::LlamaCpp => None,
+        }
+    }
+
+    /// The provider whose base URL is set, if it's one of them
+    fn matching(base_url: &str) -> Option<Self> {
+        let base_url = base_url.trim().trim_end_matches('/');
+
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|setup| setup.base_url().trim_end_matches('/') == base_url)
+    }
+
+    /// Everything but the API key, which is left as it was
+    fn apply(self, settings: &mut SerializedSettings) {
+        settings.provider = Provider::OpenAiCompatible;
+        settings.base_url = self.base_url().to_string();
+        settings.endpoint = Endpoint::ChatCompletions;
+        // Newer OpenAI models reject `max_tokens`
+        settings.max_tokens_field = match self {
+            QuickSetup::OpenAi => MaxTokensField::MaxCompletionTokens,
+            _ => MaxTokensField::MaxTokens,
+        };
+    }
+}
+
+impl Display for QuickSetup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QuickSetup::OpenAi => "OpenAI",
+            QuickSetup::Together => "Together",
+            QuickSetup::Groq => "Groq",
+            QuickSetup::OpenRouter => "OpenRouter",
+            QuickSetup::Ollama => "Ollama (local)",
+            QuickSetup::LlamaCpp => "llama.cpp server (local)",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeMode {
+    #[default]
+    Dark,
+    Light,
+    /// Follows the system's dark or light preference, live
+    Auto,
+}
+
+impl ThemeMode {
+    pub const ALL: &'static [ThemeMode] = &[ThemeMode::Dark, ThemeMode::Light, ThemeMode::Auto];
+}
+
+impl Display for ThemeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ThemeMode::Dark => "Dark",
+            ThemeMode::Light => "Light",
+            ThemeMode::Auto => "Auto (follow the system)",
+        })
+    }
+}
+
+/// The params a conversation was generated with, saved along with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationParams {
+    pub model: String,
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub max_tokens_field: MaxTokensField,
+    pub temperature: f32,
+    #[serde(default)]
+    pub endpoint: Endpoint,
+}
+
 fn default_ui_scale() -> f32 {
     100.0
 }
 
+fn default_font_size() -> f32 {
+    16.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_token_budget() -> Parsable<u32> {
+    Parsable::new(100_000)
+}
+
+fn default_connect_retries() -> Parsable<u32> {
+    Parsable::new(2)
+}
+
+fn default_user_agent() -> String {
+    format!("playground/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// How long the settings go without edits before they're autosaved
+const AUTOSAVE_DELAY: Duration = Duration::from_millis(800);
+
+fn default_delta_pointer() -> String {
+    openai::DEFAULT_DELTA_POINTER.to_string()
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SerializedSettings {
+    #[serde(default)]
+    pub provider: Provider,
+    #[serde(default)]
+    pub endpoint: Endpoint,
     pub base_url: String,
     pub api_key: String,
     pub model: String,
     pub max_tokens: Parsable<u32>,
+    #[serde(default)]
+    pub max_tokens_field: MaxTokensField,
     pub temperature: Parsable<f32>,
     #[serde(default = "default_ui_scale")]
     pub ui_scale: f32,
+    /// Of the message editors, unlike the UI scale it leaves the layout alone
+    #[serde(default = "default_font_size")]
+    pub font_size: f32,
+    /// A badge in the theme's colours next to the role of each message
+    #[serde(default)]
+    pub role_badges: bool,
+    #[serde(default)]
+    pub theme: ThemeMode,
+    #[serde(default)]
+    pub http_proxy: String,
+    #[serde(default)]
+    pub https_proxy: String,
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(default)]
+    pub ca_certificate_path: String,
+    /// Sent with every request, some gateways log or filter by it
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Expanded when sending, wherever the model was set
+    #[serde(default)]
+    pub model_aliases: Vec<ModelAlias>,
+    /// Unstick from the bottom when scrolling up, and stick again when scrolling back down
+    #[serde(default = "default_true")]
+    pub auto_stick_to_bottom: bool,
+    /// Whose tokenizer counts the tokens, blank for the model being run. Models without a known
+    /// tokenizer are estimated.
+    #[serde(default)]
+    pub tokenizer_model: String,
+    /// The run button warns once the estimated prompt tokens approach this
+    #[serde(default = "default_token_budget")]
+    pub token_budget: Parsable<u32>,
+    /// Milliseconds to coalesce streamed deltas over, 0 pastes each delta as it arrives
+    #[serde(default)]
+    pub delta_batch_interval: Parsable<u64>,
+    /// Characters per second streamed responses are revealed at, however fast they arrive. 0
+    /// pastes them as they arrive.
+    #[serde(default)]
+    pub typewriter_rate: Parsable<u32>,
+    /// Milliseconds between the starts of requests across every tab, 0 for no spacing
+    #[serde(default)]
+    pub request_interval: Parsable<u64>,
+    /// Requests streaming at once across every tab, later runs wait for a slot. 0 for no limit.
+    #[serde(default)]
+    pub max_concurrent_requests: Parsable<usize>,
+    /// Quick retries of failing to connect, before anything is sent. Failed responses are never
+    /// retried since the generation might have started.
+    #[serde(default = "default_connect_retries")]
+    pub connect_retries: Parsable<u32>,
+    /// Strips the whitespace some models start their responses with
+    #[serde(default)]
+    pub trim_leading_whitespace: bool,
+    /// Strips the trailing whitespace of each message as it's sent, the editors are left alone
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+    /// Fills in `{{now}}`, `{{date}}` and `{{env:VAR}}` in sent messages, the editors are left
+    /// alone
+    #[serde(default)]
+    pub fill_placeholders: bool,
+    /// Applied to the assistant message once the model finishes it
+    #[serde(default)]
+    pub post_processors: Vec<PostProcessor>,
+    #[serde(default)]
+    pub logprobs: bool,
+    /// Alternatives to return for each token when requesting logprobs
+    #[serde(default)]
+    pub top_logprobs: Parsable<u8>,
+    /// Sent as a structured outputs `response_format` if not blank
+    #[serde(default)]
+    pub json_schema: String,
+    /// Flags responses that don't match `json_schema`
+    #[serde(default)]
+    pub validate_json_schema: bool,
+    /// Only the last N messages are sent (plus a leading system message), 0 sends them all
+    #[serde(default)]
+    pub context_window_messages: Parsable<usize>,
+    /// Aborts the stream once the response is estimated to reach this many tokens, for backends
+    /// that ignore max_tokens. 0 doesn't stop early.
+    #[serde(default)]
+    pub stop_after_tokens: Parsable<usize>,
+    /// Past this many lines the start of a streaming message is cut from its editor, which gets
+    /// slow with very long text. The message keeps the whole text. 0 never cuts.
+    #[serde(default)]
+    pub stream_line_cap: Parsable<usize>,
+    /// Past this many messages the oldest are hidden from the view and no longer sent, a leading
+    /// system message stays. 0 keeps every message.
+    #[serde(default)]
+    pub max_messages: Parsable<usize>,
+    /// Snapshots include the hidden messages rather than only those in view
+    #[serde(default = "default_true")]
+    pub save_hidden_messages: bool,
+    /// Leaves out a trailing assistant message for backends that don't accept one, so the response
+    /// goes into a new message instead of continuing it
+    #[serde(default)]
+    pub drop_trailing_assistant: bool,
+    /// Compatibility option for backends that expect an empty assistant message to respond in
+    /// after a trailing user message, strict backends reject it
+    #[serde(default)]
+    pub empty_assistant_turn: bool,
+    /// Sent as the request's `user` field if not blank
+    #[serde(default)]
+    pub user: String,
+    /// Starts new conversations with a system message, if not blank
+    #[serde(default)]
+    pub default_system_prompt: String,
+    /// Sent with every request if not blank, for comparing runs
+    #[serde(default)]
+    pub seed: String,
+    /// Comma separated, `\n` stands for a newline and `\,` for a comma. Not sent if blank.
+    #[serde(default)]
+    pub stop_sequences: String,
+    #[serde(default)]
+    pub stop_format: StopFormat,
+    /// Starts new conversations instead of the lone user message if not empty, takes precedence
+    /// over the default system prompt
+    #[serde(default)]
+    pub conversation_template: Vec<openai::Message>,
+    /// Runs respond in a new assistant message unless the last message is one, otherwise
+    /// responses are appended to the last message
+    #[serde(default = "default_true")]
+    pub auto_add_assistant_message: bool,
+    /// Requests and raw responses are appended here if not blank, includes the prompts
+    #[serde(default)]
+    pub raw_log_path: String,
+    /// JSON pointer to the content of each chat completions delta, for providers that put it
+    /// elsewhere. Deltas without it are read from the standard place.
+    #[serde(default = "default_delta_pointer")]
+    pub delta_pointer: String,
+    /// Replaces the endpoint's path if not blank, for deployments that route by model in the
+    /// path. `{model}` is filled in with the model.
+    #[serde(default)]
+    pub path_template: String,
+    /// Whether the model is still sent in the body when the path template has it
+    #[serde(default = "default_true")]
+    pub model_in_body: bool,
+    /// Changes are saved shortly after they're made, as long as they're valid
+    #[serde(default)]
+    pub autosave: bool,
+    /// Saved as soon as it's toggled, not with the rest of the settings
+    #[serde(default = "default_true")]
+    pub settings_visible: bool,
+}
+
+impl SerializedSettings {
+    /// The full id of an aliased model, anything else is passed through unchanged
+    pub fn expand_model_alias(&self, model: String) -> String {
+        self.model_aliases
+            .iter()
+            .find(|alias| alias.alias == model.trim())
+            .map_or(model, |alias| alias.model.clone())
+    }
+
+    /// Short name of where requests go, the base URL's host for OpenAI compatible APIs
+    pub fn provider_label(&self) -> String {
+        match self.provider {
+            Provider::Mock => "Mock".to_string(),
+            Provider::OpenAiCompatible => reqwest::Url::parse(self.base_url.trim())
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| "No base URL".to_string()),
+        }
+    }
+
+    /// `None` if blank or not a number, which the settings can't be saved with
+    pub fn seed(&self) -> Option<i64> {
+        self.seed.trim().parse().ok()
+    }
+
+    pub fn stop_sequences(&self) -> Vec<String> {
+        parse_stop_sequences(&self.stop_sequences)
+    }
+
+    /// Set if the API key doesn't look like one of the provider the base URL is for, doesn't
+    /// stop the settings from being saved
+    fn api_key_hint(&self) -> Option<String> {
+        let api_key = self.api_key.trim();
+
+        // Keys read from files aren't known until the request
+        if self.provider == Provider::Mock || api_key.is_empty() || api_key.starts_with("file:") {
+            return None;
+        }
+
+        let setup = QuickSetup::matching(&self.base_url)?;
+        let prefix = setup.key_prefix()?;
+
+        (!api_key.starts_with(prefix))
+            .then(|| format!("{setup} keys usually start with {prefix}, is this the right key?"))
+    }
+
+    pub fn routing(&self) -> Routing {
+        Routing {
+            path_template: self.path_template.clone(),
+            model_in_body: self.model_in_body,
+        }
+    }
+
+    pub fn connect_retries(&self) -> u32 {
+        self.connect_retries.parsed().unwrap_or_default()
+    }
+
+    pub fn queue_limits(&self) -> Limits {
+        Limits {
+            min_interval: Duration::from_millis(self.request_interval.parsed().unwrap_or_default()),
+            max_concurrent: self.max_concurrent_requests.parsed().unwrap_or_default(),
+        }
+    }
+
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            http_proxy: self.http_proxy.clone(),
+            https_proxy: self.https_proxy.clone(),
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs,
+            ca_certificate_path: self.ca_certificate_path.clone(),
+            user_agent: self.user_agent.trim().to_string(),
+        }
+    }
 }
 
 impl Default for SerializedSettings {
     fn default() -> Self {
         Self {
+            provider: Default::default(),
+            endpoint: Default::default(),
             base_url: "".to_string(),
             api_key: "".to_string(),
             model: "".to_string(),
             max_tokens: Parsable::new(1000),
+            max_tokens_field: Default::default(),
             temperature: Default::default(),
             ui_scale: default_ui_scale(),
+            font_size: default_font_size(),
+            role_badges: false,
+            theme: Default::default(),
+            http_proxy: "".to_string(),
+            https_proxy: "".to_string(),
+            danger_accept_invalid_certs: false,
+            ca_certificate_path: "".to_string(),
+            user_agent: default_user_agent(),
+            presets: vec![],
+            model_aliases: vec![],
+            auto_stick_to_bottom: true,
+            tokenizer_model: "".to_string(),
+            token_budget: default_token_budget(),
+            delta_batch_interval: Default::default(),
+            typewriter_rate: Default::default(),
+            request_interval: Default::default(),
+            max_concurrent_requests: Default::default(),
+            connect_retries: default_connect_retries(),
+            trim_leading_whitespace: false,
+            trim_trailing_whitespace: false,
+            fill_placeholders: false,
+            post_processors: vec![],
+            logprobs: false,
+            top_logprobs: Default::default(),
+            json_schema: "".to_string(),
+            validate_json_schema: false,
+            context_window_messages: Default::default(),
+            stop_after_tokens: Default::default(),
+            stream_line_cap: Default::default(),
+            max_messages: Default::default(),
+            save_hidden_messages: true,
+            drop_trailing_assistant: false,
+            empty_assistant_turn: false,
+            user: "".to_string(),
+            default_system_prompt: "".to_string(),
+            seed: "".to_string(),
+            stop_sequences: "".to_string(),
+            stop_format: Default::default(),
+            conversation_template: vec![],
+            auto_add_assistant_message: true,
+            raw_log_path: "".to_string(),
+            delta_pointer: default_delta_pointer(),
+            path_template: "".to_string(),
+            model_in_body: true,
+            autosave: false,
+            settings_visible: true,
         }
     }
 }
@@ -119,32 +643,139 @@ impl Default for SerializedSettings {
 #[derive(Debug, Clone)]
 pub enum SettingsMessage {
     Load(Box<SerializedSettings>),
+    ProviderChanged(Provider),
+    QuickSetup(QuickSetup),
+    EndpointChanged(Endpoint),
     BaseUrlChanged(String),
     ApiKeyChanged(String),
     ModelChanged(String),
     MaxTokensChanged(Parsable<u32>),
+    MaxTokensFieldChanged(MaxTokensField),
     TemperatureChanged(Parsable<f32>),
     UiScaleChanged(f32),
+    FontSizeChanged(f32),
+    RoleBadgesChanged(bool),
+    ThemeChanged(ThemeMode),
+    AutoStickToBottomChanged(bool),
+    TokenizerModelChanged(String),
+    TokenBudgetChanged(Parsable<u32>),
+    DeltaBatchIntervalChanged(Parsable<u64>),
+    TypewriterRateChanged(Parsable<u32>),
+    RequestIntervalChanged(Parsable<u64>),
+    MaxConcurrentRequestsChanged(Parsable<usize>),
+    ConnectRetriesChanged(Parsable<u32>),
+    TrimLeadingWhitespaceChanged(bool),
+    TrimTrailingWhitespaceChanged(bool),
+    FillPlaceholdersChanged(bool),
+    PostProcessorToggled(PostProcessor, bool),
+    LogprobsChanged(bool),
+    TopLogprobsChanged(Parsable<u8>),
+    JsonSchemaChanged(String),
+    ValidateJsonSchemaChanged(bool),
+    ContextWindowMessagesChanged(Parsable<usize>),
+    StopAfterTokensChanged(Parsable<usize>),
+    StreamLineCapChanged(Parsable<usize>),
+    MaxMessagesChanged(Parsable<usize>),
+    SaveHiddenMessagesChanged(bool),
+    DropTrailingAssistantChanged(bool),
+    EmptyAssistantTurnChanged(bool),
+    UserChanged(String),
+    DefaultSystemPromptChanged(String),
+    SeedChanged(String),
+    StopSequencesChanged(String),
+    StopFormatChanged(StopFormat),
+    /// From a conversation, empty to start new conversations as usual
+    ConversationTemplateChanged(Vec<openai::Message>),
+    AutoAddAssistantMessageChanged(bool),
+    HttpProxyChanged(String),
+    HttpsProxyChanged(String),
+    DangerAcceptInvalidCertsChanged(bool),
+    CaCertificatePathChanged(String),
+    UserAgentChanged(String),
+    RawLogPathChanged(String),
+    DeltaPointerChanged(String),
+    PathTemplateChanged(String),
+    ModelInBodyChanged(bool),
+    PresetNameChanged(String),
+    PresetSystemPromptChanged(String),
+    AddPreset,
+    DeletePreset(usize),
+    /// Into the live settings, from an imported conversation
+    ApplyGenerationParams(Box<GenerationParams>),
+    NewAliasChanged(String),
+    NewAliasModelChanged(String),
+    AddModelAlias,
+    DeleteModelAlias(usize),
+    TransferPathChanged(String),
+    ExportSettings,
+    ExportResult(Result<(), String>),
+    ImportSettings,
+    ImportResult(Result<Box<SerializedSettings>, String>),
+    ToggleChanges,
     Save,
     SaveResult(Result<Box<SerializedSettings>, String>),
+    ClientBuilt(Box<BuiltClient>),
+    SettingsVisibleChanged(bool),
+    SettingsVisibleSaved(Result<(), String>),
+    AutosaveChanged(bool),
+    AutosaveTick,
+}
+
+/// A field whose live value differs from its saved value
+#[derive(Debug, Clone, PartialEq)]
+struct SettingChange {
+    field: String,
+    saved: String,
+    live: String,
 }
 
-async fn load_existing_settings() -> anyhow::Result<SerializedSettings> {
-    let data = tokio::fs::read("settings.json").await?;
+/// Short form of a serialized setting, secrets are hidden
+fn display_setting(field: &str, value: Option<&Value>) -> String {
+    let text = match value {
+        _ if field == "api_key" => "(hidden)".to_string(),
+        None | Some(Value::Null) => "(none)".to_string(),
+        Some(Value::String(string)) if string.is_empty() => "(blank)".to_string(),
+        Some(Value::String(string)) => string.clone(),
+        // Parsables are shown as typed
+        Some(Value::Object(object)) if object.contains_key("parsed") => {
+            display_setting(field, object.get("content"))
+        }
+        Some(value) => value.to_string(),
+    };
 
-    serde_json::from_slice::<SerializedSettings>(data.as_slice()).map_err(Into::into)
+    match text.chars().count() > 60 {
+        true => format!("{}…", text.chars().take(60).collect::<String>()),
+        false => text,
+    }
+}
+
+const SETTINGS_PATH: &str = "settings.json";
+
+pub async fn load_existing_settings() -> anyhow::Result<SerializedSettings> {
+    read_settings(SETTINGS_PATH.to_string()).await
 }
 
 async fn save_settings(
     serialized_settings: SerializedSettings,
 ) -> anyhow::Result<SerializedSettings> {
-    tokio::fs::write(
-        "settings.json",
-        serde_json::to_string_pretty(&serialized_settings)?,
-    )
-    .await
-    .map(|_| serialized_settings)
-    .map_err(Into::into)
+    write_settings(SETTINGS_PATH.to_string(), serialized_settings).await
+}
+
+/// Fields missing from older or newer settings files fall back to their serde defaults
+async fn read_settings(path: String) -> anyhow::Result<SerializedSettings> {
+    let data = tokio::fs::read(path).await?;
+
+    serde_json::from_slice::<SerializedSettings>(data.as_slice()).map_err(Into::into)
+}
+
+async fn write_settings(
+    path: String,
+    serialized_settings: SerializedSettings,
+) -> anyhow::Result<SerializedSettings> {
+    tokio::fs::write(path, serde_json::to_string_pretty(&serialized_settings)?)
+        .await
+        .map(|_| serialized_settings)
+        .map_err(Into::into)
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -153,22 +784,173 @@ pub struct SettingsState {
     saved_settings: SerializedSettings,
     /// Presented in the UI, may not be saved.
     live_settings: SerializedSettings,
+    /// Built from the saved settings, in the background once they're loaded or saved
+    #[serde(skip)]
+    client: HttpClient,
+    /// Shared by the runs of every tab
+    #[serde(skip)]
+    queue: Arc<RequestQueue>,
+    /// The preset being created from the live generation params
+    #[serde(skip)]
+    new_preset_name: String,
+    #[serde(skip)]
+    new_preset_system_prompt: String,
+    /// The alias being added
+    #[serde(skip)]
+    new_alias: ModelAlias,
+    /// File the settings are exported to and imported from
+    #[serde(skip)]
+    transfer_path: String,
+    /// Outcome of the last export or import
+    #[serde(skip)]
+    transfer_status: Option<Result<String, String>>,
+    /// Lists the unsaved changes above the save button
+    #[serde(skip)]
+    show_changes: bool,
+    /// Autosaving waits for this to be `AUTOSAVE_DELAY` ago, so typing isn't saved per keystroke
+    #[serde(skip)]
+    last_edited: Option<Instant>,
 }
 
 impl SettingsState {
-    fn valid_parsables(&self) -> bool {
+    fn new(settings: SerializedSettings) -> Self {
+        Self {
+            saved_settings: settings.clone(),
+            live_settings: settings,
+            client: HttpClient::default(),
+            queue: Arc::default(),
+            new_preset_name: "".to_string(),
+            new_preset_system_prompt: "".to_string(),
+            new_alias: ModelAlias {
+                alias: "".to_string(),
+                model: "".to_string(),
+            },
+            transfer_path: "".to_string(),
+            transfer_status: None,
+            show_changes: false,
+            last_edited: None,
+        }
+    }
+
+    /// Only replaces the saved settings, edits made while they were being written stay live and
+    /// count as unsaved. The client has to be rebuilt after.
+    fn saved_as(&mut self, settings: SerializedSettings) {
+        self.saved_settings = settings;
+    }
+
+    /// Builds the client for the saved settings unless it was already built for their options
+    fn rebuild_client(&self) -> Task<PlaygroundMessage> {
+        let options = self.saved_settings.client_options();
+
+        if !self.client.outdated(&options) {
+            return Task::none();
+        }
+
+        Task::future(BuiltClient::build(options))
+            .map(|built| PlaygroundMessage::Settings(SettingsMessage::ClientBuilt(Box::new(built))))
+    }
+
+    pub fn valid_inputs(&self) -> bool {
+        let settings = &self.live_settings;
+
+        settings.max_tokens.is_valid()
+            && settings.temperature.is_valid()
+            && settings.token_budget.is_valid()
+            && settings.delta_batch_interval.is_valid()
+            && settings.typewriter_rate.is_valid()
+            && settings.request_interval.is_valid()
+            && settings.max_concurrent_requests.is_valid()
+            && settings.connect_retries.is_valid()
+            && settings.top_logprobs.is_valid()
+            && schema::parse(&settings.json_schema).is_ok()
+            && settings.context_window_messages.is_valid()
+            && settings.stop_after_tokens.is_valid()
+            && settings.stream_line_cap.is_valid()
+            && settings.max_messages.is_valid()
+            && openai::valid_proxy(&settings.http_proxy)
+            && openai::valid_proxy(&settings.https_proxy)
+            && openai::valid_json_pointer(&settings.delta_pointer)
+            && openai::valid_user_agent(settings.user_agent.trim())
+            && valid_seed(&settings.seed)
+    }
+
+    /// Creates a preset from the live generation params, if they're valid and the name isn't empty
+    fn new_preset(&self) -> Option<Preset> {
         let settings = &self.live_settings;
 
-        settings.max_tokens.is_valid() && settings.temperature.is_valid()
+        if self.new_preset_name.is_empty() {
+            return None;
+        }
+
+        Some(Preset {
+            name: self.new_preset_name.clone(),
+            model: settings.model.clone(),
+            max_tokens: settings.max_tokens.parsed()?,
+            temperature: settings.temperature.parsed()?,
+            system_prompt: self.new_preset_system_prompt.clone(),
+        })
+    }
+
+    /// The alias being added, if neither side is blank
+    fn new_alias(&self) -> Option<ModelAlias> {
+        let alias = self.new_alias.alias.trim();
+        let model = self.new_alias.model.trim();
+
+        (!alias.is_empty() && !model.is_empty()).then(|| ModelAlias {
+            alias: alias.to_string(),
+            model: model.to_string(),
+        })
     }
 
-    fn unsaved_changes(&self) -> bool {
+    pub fn unsaved_changes(&self) -> bool {
         self.saved_settings != self.live_settings
     }
 
+    /// Whether unsaved changes would be saved without asking, once they settle
+    pub fn autosaves(&self) -> bool {
+        self.live_settings.autosave && self.valid_inputs()
+    }
+
+    /// Whether the live settings have settled and should be saved without pressing save
+    fn autosave_due(&self) -> bool {
+        self.live_settings.autosave
+            && self.unsaved_changes()
+            && self.valid_inputs()
+            && self
+                .last_edited
+                .is_some_and(|edited| edited.elapsed() >= AUTOSAVE_DELAY)
+    }
+
+    /// Compared by their serialized values, so every field is covered without listing them
+    fn changes(&self) -> Vec<SettingChange> {
+        let (Ok(Value::Object(saved)), Ok(Value::Object(live))) = (
+            serde_json::to_value(&self.saved_settings),
+            serde_json::to_value(&self.live_settings),
+        ) else {
+            return vec![];
+        };
+
+        live.iter()
+            .filter(|(field, value)| saved.get(*field) != Some(*value))
+            .map(|(field, value)| SettingChange {
+                field: field.clone(),
+                saved: display_setting(field, saved.get(field)),
+                live: display_setting(field, Some(value)),
+            })
+            .collect()
+    }
+
     pub fn saved(&self) -> &SerializedSettings {
         &self.saved_settings
     }
+
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    pub fn queue(&self) -> Arc<RequestQueue> {
+        self.queue.clone()
+    }
 }
 
 pub enum SettingsView {
@@ -195,20 +977,46 @@ impl SettingsView {
     }
 
     fn update_settings<F: FnOnce(&mut SerializedSettings)>(&mut self, f: F) {
+        self.update_state(|state| f(&mut state.live_settings))
+    }
+
+    fn update_state<F: FnOnce(&mut SettingsState)>(&mut self, f: F) {
         if let SettingsView::Loaded(state) = self {
-            f(&mut state.live_settings)
+            f(state);
+
+            // Edits to anything but the settings only hold an autosave back a little
+            state.last_edited = Some(Instant::now());
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<SettingsMessage> {
+        match self {
+            SettingsView::Loaded(state)
+                if state.live_settings.autosave && state.last_edited.is_some() =>
+            {
+                time::every(AUTOSAVE_DELAY / 4).map(|_| SettingsMessage::AutosaveTick)
+            }
+            _ => Subscription::none(),
         }
     }
 
     pub fn update(&mut self, message: SettingsMessage) -> Task<PlaygroundMessage> {
         match message {
             SettingsMessage::Load(state) => {
-                let state = *state;
+                let state = SettingsState::new(*state);
+                let task = state.rebuild_client();
 
-                *self = SettingsView::Loaded(SettingsState {
-                    saved_settings: state.clone(),
-                    live_settings: state,
-                });
+                *self = SettingsView::Loaded(state);
+
+                task
+            }
+            SettingsMessage::ProviderChanged(provider) => {
+                self.update_settings(|settings| settings.provider = provider);
+
+                Task::none()
+            }
+            SettingsMessage::EndpointChanged(endpoint) => {
+                self.update_settings(|settings| settings.endpoint = endpoint);
 
                 Task::none()
             }
@@ -232,6 +1040,11 @@ impl SettingsView {
 
                 Task::none()
             }
+            SettingsMessage::MaxTokensFieldChanged(field) => {
+                self.update_settings(|settings| settings.max_tokens_field = field);
+
+                Task::none()
+            }
             SettingsMessage::TemperatureChanged(temperature) => {
                 self.update_settings(|settings| settings.temperature = temperature);
 
@@ -242,97 +1055,700 @@ impl SettingsView {
 
                 Task::none()
             }
-            SettingsMessage::Save => {
-                let new_settings = self.settings().live_settings.clone();
+            SettingsMessage::FontSizeChanged(size) => {
+                self.update_settings(|settings| settings.font_size = size);
 
-                Task::future(save_settings(new_settings)).map(|settings| {
-                    PlaygroundMessage::Settings(SettingsMessage::SaveResult(
-                        settings.map(Box::new).map_err(|err| err.to_string()),
-                    ))
-                })
+                Task::none()
             }
-            SettingsMessage::SaveResult(res) => {
-                // Ignore the error for now
-                if let Ok(new_settings) = res {
-                    let new_settings = *new_settings;
+            SettingsMessage::RoleBadgesChanged(role_badges) => {
+                self.update_settings(|settings| settings.role_badges = role_badges);
 
-                    *self = SettingsView::Loaded(SettingsState {
-                        saved_settings: new_settings.clone(),
-                        live_settings: new_settings,
-                    });
-                }
+                Task::none()
+            }
+            SettingsMessage::ThemeChanged(theme) => {
+                self.update_settings(|settings| settings.theme = theme);
 
                 Task::none()
             }
-        }
-    }
+            SettingsMessage::AutoStickToBottomChanged(auto) => {
+                self.update_settings(|settings| settings.auto_stick_to_bottom = auto);
 
-    pub fn view(&self) -> Container<SettingsMessage> {
-        container(match self {
-            SettingsView::Loading => {
-                Element::from(container(text("Loading Settings...")).center(Length::Fill))
+                Task::none()
             }
-            SettingsView::Loaded(settings_state) => {
-                let SerializedSettings {
-                    base_url,
-                    api_key,
-                    model,
-                    max_tokens,
-                    temperature,
-                    ui_scale,
-                } = &settings_state.live_settings;
+            SettingsMessage::TokenizerModelChanged(model) => {
+                self.update_settings(|settings| settings.tokenizer_model = model);
 
-                column([
-                    pair_in_column(
-                        "Base URL",
-                        text_input("e.g. https://api.openai.com/", base_url)
-                            .on_input(SettingsMessage::BaseUrlChanged),
-                    )
-                    .spacing(5)
-                    .into(),
-                    pair_in_column(
-                        "API Key",
-                        text_input("", api_key)
-                            .secure(true)
-                            .on_input(SettingsMessage::ApiKeyChanged),
-                    )
-                    .spacing(5)
-                    .into(),
-                    pair_in_column(
-                        "Model",
-                        text_input("Model ID e.g. gpt-4o-mini", model)
-                            .on_input(SettingsMessage::ModelChanged),
-                    )
-                    .spacing(4.99) // weird clipping shit with text input
-                    .into(),
-                    pair_in_column(
-                        "Max Tokens",
-                        parsable_text_input(
-                            "e.g. 1000",
-                            max_tokens,
-                            SettingsMessage::MaxTokensChanged,
-                        ),
-                    )
-                    .spacing(5)
-                    .into(),
-                    pair_in_column(
-                        "Temperature",
-                        parsable_text_input(
-                            "e.g. 1.0",
-                            temperature,
-                            SettingsMessage::TemperatureChanged,
-                        ),
-                    )
-                    .spacing(5)
-                    .into(),
-                    pair_in_column(
-                        row([
-                            container(text(format!("UI Scale ({}%)", ui_scale)))
-                                .padding(Padding {
-                                    top: 5.0,
-                                    bottom: 5.0,
-                                    right: 0.0,
-                                    left: 0.0,
+                Task::none()
+            }
+            SettingsMessage::TokenBudgetChanged(budget) => {
+                self.update_settings(|settings| settings.token_budget = budget);
+
+                Task::none()
+            }
+            SettingsMessage::DeltaBatchIntervalChanged(interval) => {
+                self.update_settings(|settings| settings.delta_batch_interval = interval);
+
+                Task::none()
+            }
+            SettingsMessage::TypewriterRateChanged(rate) => {
+                self.update_settings(|settings| settings.typewriter_rate = rate);
+
+                Task::none()
+            }
+            SettingsMessage::RequestIntervalChanged(interval) => {
+                self.update_settings(|settings| settings.request_interval = interval);
+
+                Task::none()
+            }
+            SettingsMessage::ConnectRetriesChanged(retries) => {
+                self.update_settings(|settings| settings.connect_retries = retries);
+
+                Task::none()
+            }
+            SettingsMessage::MaxConcurrentRequestsChanged(max) => {
+                self.update_settings(|settings| settings.max_concurrent_requests = max);
+
+                Task::none()
+            }
+            SettingsMessage::TrimLeadingWhitespaceChanged(trim) => {
+                self.update_settings(|settings| settings.trim_leading_whitespace = trim);
+
+                Task::none()
+            }
+            SettingsMessage::TrimTrailingWhitespaceChanged(trim) => {
+                self.update_settings(|settings| settings.trim_trailing_whitespace = trim);
+
+                Task::none()
+            }
+            SettingsMessage::FillPlaceholdersChanged(fill) => {
+                self.update_settings(|settings| settings.fill_placeholders = fill);
+
+                Task::none()
+            }
+            SettingsMessage::PostProcessorToggled(processor, enabled) => {
+                self.update_settings(|settings| {
+                    settings.post_processors.retain(|kept| *kept != processor);
+
+                    if enabled {
+                        settings.post_processors.push(processor);
+                    }
+                });
+
+                Task::none()
+            }
+            SettingsMessage::LogprobsChanged(logprobs) => {
+                self.update_settings(|settings| settings.logprobs = logprobs);
+
+                Task::none()
+            }
+            SettingsMessage::JsonSchemaChanged(schema) => {
+                self.update_settings(|settings| settings.json_schema = schema);
+
+                Task::none()
+            }
+            SettingsMessage::ValidateJsonSchemaChanged(validate) => {
+                self.update_settings(|settings| settings.validate_json_schema = validate);
+
+                Task::none()
+            }
+            SettingsMessage::TopLogprobsChanged(top_logprobs) => {
+                self.update_settings(|settings| settings.top_logprobs = top_logprobs);
+
+                Task::none()
+            }
+            SettingsMessage::ContextWindowMessagesChanged(messages) => {
+                self.update_settings(|settings| settings.context_window_messages = messages);
+
+                Task::none()
+            }
+            SettingsMessage::StopAfterTokensChanged(tokens) => {
+                self.update_settings(|settings| settings.stop_after_tokens = tokens);
+
+                Task::none()
+            }
+            SettingsMessage::StreamLineCapChanged(lines) => {
+                self.update_settings(|settings| settings.stream_line_cap = lines);
+
+                Task::none()
+            }
+            SettingsMessage::MaxMessagesChanged(max_messages) => {
+                self.update_settings(|settings| settings.max_messages = max_messages);
+
+                Task::none()
+            }
+            SettingsMessage::SaveHiddenMessagesChanged(save) => {
+                self.update_settings(|settings| settings.save_hidden_messages = save);
+
+                Task::none()
+            }
+            SettingsMessage::DropTrailingAssistantChanged(drop) => {
+                self.update_settings(|settings| settings.drop_trailing_assistant = drop);
+
+                Task::none()
+            }
+            SettingsMessage::EmptyAssistantTurnChanged(empty_turn) => {
+                self.update_settings(|settings| settings.empty_assistant_turn = empty_turn);
+
+                Task::none()
+            }
+            SettingsMessage::UserChanged(user) => {
+                self.update_settings(|settings| settings.user = user);
+
+                Task::none()
+            }
+            SettingsMessage::DefaultSystemPromptChanged(prompt) => {
+                self.update_settings(|settings| settings.default_system_prompt = prompt);
+
+                Task::none()
+            }
+            SettingsMessage::SeedChanged(seed) => {
+                self.update_settings(|settings| settings.seed = seed);
+
+                Task::none()
+            }
+            SettingsMessage::StopSequencesChanged(stop_sequences) => {
+                self.update_settings(|settings| settings.stop_sequences = stop_sequences);
+
+                Task::none()
+            }
+            SettingsMessage::StopFormatChanged(stop_format) => {
+                self.update_settings(|settings| settings.stop_format = stop_format);
+
+                Task::none()
+            }
+            SettingsMessage::ConversationTemplateChanged(template) => {
+                self.update_settings(|settings| settings.conversation_template = template);
+
+                Task::none()
+            }
+            SettingsMessage::AutoAddAssistantMessageChanged(auto_add) => {
+                self.update_settings(|settings| settings.auto_add_assistant_message = auto_add);
+
+                Task::none()
+            }
+            SettingsMessage::HttpProxyChanged(proxy) => {
+                self.update_settings(|settings| settings.http_proxy = proxy);
+
+                Task::none()
+            }
+            SettingsMessage::HttpsProxyChanged(proxy) => {
+                self.update_settings(|settings| settings.https_proxy = proxy);
+
+                Task::none()
+            }
+            SettingsMessage::DangerAcceptInvalidCertsChanged(accept) => {
+                self.update_settings(|settings| settings.danger_accept_invalid_certs = accept);
+
+                Task::none()
+            }
+            SettingsMessage::CaCertificatePathChanged(path) => {
+                self.update_settings(|settings| settings.ca_certificate_path = path);
+
+                Task::none()
+            }
+            SettingsMessage::UserAgentChanged(user_agent) => {
+                self.update_settings(|settings| settings.user_agent = user_agent);
+
+                Task::none()
+            }
+            SettingsMessage::RawLogPathChanged(path) => {
+                self.update_settings(|settings| settings.raw_log_path = path);
+
+                Task::none()
+            }
+            SettingsMessage::DeltaPointerChanged(pointer) => {
+                self.update_settings(|settings| settings.delta_pointer = pointer);
+
+                Task::none()
+            }
+            SettingsMessage::PathTemplateChanged(template) => {
+                self.update_settings(|settings| settings.path_template = template);
+
+                Task::none()
+            }
+            SettingsMessage::ModelInBodyChanged(model_in_body) => {
+                self.update_settings(|settings| settings.model_in_body = model_in_body);
+
+                Task::none()
+            }
+            SettingsMessage::PresetNameChanged(name) => {
+                self.update_state(|state| state.new_preset_name = name);
+
+                Task::none()
+            }
+            SettingsMessage::PresetSystemPromptChanged(system_prompt) => {
+                self.update_state(|state| state.new_preset_system_prompt = system_prompt);
+
+                Task::none()
+            }
+            SettingsMessage::AddPreset => {
+                self.update_state(|state| {
+                    if let Some(preset) = state.new_preset() {
+                        // Presets are picked by name, so a preset with the same name is replaced
+                        state
+                            .live_settings
+                            .presets
+                            .retain(|existing| existing.name != preset.name);
+                        state.live_settings.presets.push(preset);
+
+                        state.new_preset_name.clear();
+                        state.new_preset_system_prompt.clear();
+                    }
+                });
+
+                Task::none()
+            }
+            SettingsMessage::DeletePreset(index) => {
+                self.update_settings(|settings| {
+                    settings.presets.remove(index);
+                });
+
+                Task::none()
+            }
+            SettingsMessage::NewAliasChanged(alias) => {
+                self.update_state(|state| state.new_alias.alias = alias);
+
+                Task::none()
+            }
+            SettingsMessage::NewAliasModelChanged(model) => {
+                self.update_state(|state| state.new_alias.model = model);
+
+                Task::none()
+            }
+            SettingsMessage::AddModelAlias => {
+                self.update_state(|state| {
+                    if let Some(alias) = state.new_alias() {
+                        // An alias expands to a single model, so it's replaced
+                        state
+                            .live_settings
+                            .model_aliases
+                            .retain(|existing| existing.alias != alias.alias);
+                        state.live_settings.model_aliases.push(alias);
+
+                        state.new_alias.alias.clear();
+                        state.new_alias.model.clear();
+                    }
+                });
+
+                Task::none()
+            }
+            SettingsMessage::DeleteModelAlias(index) => {
+                self.update_settings(|settings| {
+                    settings.model_aliases.remove(index);
+                });
+
+                Task::none()
+            }
+            SettingsMessage::QuickSetup(setup) => {
+                self.update_settings(|settings| setup.apply(settings));
+
+                Task::none()
+            }
+            SettingsMessage::ApplyGenerationParams(params) => {
+                self.update_settings(|settings| {
+                    settings.model = params.model;
+                    settings.max_tokens = Parsable::new(params.max_tokens);
+                    settings.max_tokens_field = params.max_tokens_field;
+                    settings.temperature = Parsable::new(params.temperature);
+                    settings.endpoint = params.endpoint;
+                });
+
+                Task::none()
+            }
+            SettingsMessage::TransferPathChanged(path) => {
+                self.update_state(|state| state.transfer_path = path);
+
+                Task::none()
+            }
+            SettingsMessage::ExportSettings => {
+                let settings = self.settings();
+
+                Task::future(write_settings(
+                    settings.transfer_path.clone(),
+                    settings.saved_settings.clone(),
+                ))
+                .map(|res| {
+                    PlaygroundMessage::Settings(SettingsMessage::ExportResult(
+                        res.map(|_| ()).map_err(|err| err.to_string()),
+                    ))
+                })
+            }
+            SettingsMessage::ExportResult(res) => {
+                self.update_state(|state| {
+                    state.transfer_status = Some(match res {
+                        Ok(()) => Ok(format!(
+                            "Exported the saved settings to {}",
+                            state.transfer_path
+                        )),
+                        Err(err) => Err(format!("Couldn't export the settings: {err}")),
+                    })
+                });
+
+                Task::none()
+            }
+            SettingsMessage::ImportSettings => {
+                Task::future(read_settings(self.settings().transfer_path.clone())).map(|res| {
+                    PlaygroundMessage::Settings(SettingsMessage::ImportResult(
+                        res.map(Box::new).map_err(|err| err.to_string()),
+                    ))
+                })
+            }
+            SettingsMessage::ImportResult(res) => {
+                // Only the live settings are replaced so they can be reviewed before saving
+                self.update_state(|state| {
+                    state.transfer_status = Some(match res {
+                        Ok(settings) => {
+                            state.live_settings = *settings;
+
+                            Ok("Imported the settings, save to keep them".to_string())
+                        }
+                        Err(err) => Err(format!("Couldn't import the settings: {err}")),
+                    })
+                });
+
+                Task::none()
+            }
+            SettingsMessage::ToggleChanges => {
+                self.update_state(|state| state.show_changes = !state.show_changes);
+
+                Task::none()
+            }
+            SettingsMessage::Save => {
+                let new_settings = self.settings().live_settings.clone();
+
+                Task::future(save_settings(new_settings)).map(|settings| {
+                    PlaygroundMessage::Settings(SettingsMessage::SaveResult(
+                        settings.map(Box::new).map_err(|err| err.to_string()),
+                    ))
+                })
+            }
+            SettingsMessage::SaveResult(res) => {
+                // Ignore the error for now
+                if let Ok(new_settings) = res {
+                    match self {
+                        SettingsView::Loading => {
+                            *self = SettingsView::Loaded(SettingsState::new(*new_settings))
+                        }
+                        SettingsView::Loaded(state) => state.saved_as(*new_settings),
+                    }
+                }
+
+                match self {
+                    SettingsView::Loaded(state) => state.rebuild_client(),
+                    SettingsView::Loading => Task::none(),
+                }
+            }
+            SettingsMessage::ClientBuilt(built) => {
+                // Settings saved while it was building have another one on the way
+                if let SettingsView::Loaded(state) = self {
+                    if *built.options() == state.saved_settings.client_options() {
+                        state.client.set(*built);
+                    }
+                }
+
+                Task::none()
+            }
+            SettingsMessage::SettingsVisibleChanged(visible) => {
+                let SettingsView::Loaded(state) = self else {
+                    return Task::none();
+                };
+
+                // Only the visibility is saved, unsaved changes stay unsaved
+                state.saved_settings.settings_visible = visible;
+                state.live_settings.settings_visible = visible;
+
+                Task::future(save_settings(state.saved_settings.clone())).map(|res| {
+                    PlaygroundMessage::Settings(SettingsMessage::SettingsVisibleSaved(
+                        res.map(|_| ()).map_err(|err| err.to_string()),
+                    ))
+                })
+            }
+            // Ignore the error, it's only the panel's visibility
+            SettingsMessage::SettingsVisibleSaved(_) => Task::none(),
+            SettingsMessage::AutosaveChanged(autosave) => {
+                self.update_settings(|settings| settings.autosave = autosave);
+
+                Task::none()
+            }
+            SettingsMessage::AutosaveTick => {
+                let SettingsView::Loaded(state) = self else {
+                    return Task::none();
+                };
+
+                if !state.autosave_due() {
+                    // Nothing more to wait for once it's saved or typed back to the saved value,
+                    // or until an edit makes the inputs valid again
+                    if !state.unsaved_changes() || !state.valid_inputs() {
+                        state.last_edited = None;
+                    }
+
+                    return Task::none();
+                }
+
+                // Edits while saving are saved after they settle in turn
+                state.last_edited = None;
+
+                self.update(SettingsMessage::Save)
+            }
+        }
+    }
+
+    /// `running` is how many tabs are inferencing, they keep the saved settings they started with
+    pub fn view(&self, running: usize) -> Container<SettingsMessage> {
+        container(match self {
+            SettingsView::Loading => {
+                Element::from(container(text("Loading Settings...")).center(Length::Fill))
+            }
+            SettingsView::Loaded(settings_state) => {
+                let SerializedSettings {
+                    provider,
+                    endpoint,
+                    base_url,
+                    api_key,
+                    model,
+                    max_tokens,
+                    max_tokens_field,
+                    temperature,
+                    ui_scale,
+                    theme,
+                    font_size,
+                    role_badges,
+                    http_proxy,
+                    https_proxy,
+                    danger_accept_invalid_certs,
+                    ca_certificate_path,
+                    user_agent,
+                    presets,
+                    model_aliases,
+                    auto_stick_to_bottom,
+                    tokenizer_model,
+                    token_budget,
+                    delta_batch_interval,
+                    typewriter_rate,
+                    request_interval,
+                    max_concurrent_requests,
+                    connect_retries,
+                    trim_leading_whitespace,
+                    trim_trailing_whitespace,
+                    fill_placeholders,
+                    post_processors,
+                    logprobs,
+                    top_logprobs,
+                    json_schema,
+                    validate_json_schema,
+                    context_window_messages,
+                    stop_after_tokens,
+                    stream_line_cap,
+                    max_messages,
+                    save_hidden_messages,
+                    drop_trailing_assistant,
+                    empty_assistant_turn,
+                    user,
+                    default_system_prompt,
+                    seed,
+                    stop_sequences,
+                    stop_format,
+                    conversation_template,
+                    auto_add_assistant_message,
+                    raw_log_path,
+                    delta_pointer,
+                    path_template,
+                    model_in_body,
+                    autosave,
+                    settings_visible: _,
+                } = &settings_state.live_settings;
+
+                let settings_column = column([
+                    match running {
+                        0 => Column::new().into(),
+                        running => container(
+                            text(match running {
+                                1 => "A run is in progress with the saved settings, changes \
+                                      apply to the next run"
+                                    .to_string(),
+                                _ => format!(
+                                    "{running} runs are in progress with the saved settings, \
+                                     changes apply to the next run"
+                                ),
+                            })
+                            .size(12)
+                            .style(|theme: &Theme| text::Style {
+                                color: Some(theme.extended_palette().background.weak.text),
+                            }),
+                        )
+                        .padding(5)
+                        .width(Length::Fill)
+                        .style(|theme: &Theme| container::Style {
+                            border: Border {
+                                color: theme.extended_palette().background.strong.color,
+                                width: 1.0,
+                                radius: 5.0.into(),
+                            },
+                            ..Default::default()
+                        })
+                        .into(),
+                    },
+                    pair_in_column(
+                        "Quick Setup",
+                        pick_list(QuickSetup::ALL, None::<QuickSetup>, SettingsMessage::QuickSetup)
+                            .placeholder("Fill in a provider's defaults, then the API key")
+                            .width(Length::Fill),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Provider",
+                        pick_list(Provider::ALL, Some(*provider), SettingsMessage::ProviderChanged)
+                            .width(Length::Fill),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Endpoint",
+                        pick_list(Endpoint::ALL, Some(*endpoint), SettingsMessage::EndpointChanged)
+                            .width(Length::Fill),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Base URL",
+                        text_input("e.g. https://api.openai.com/", base_url)
+                            .on_input(SettingsMessage::BaseUrlChanged),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "API Key",
+                        text_input("or file:/path/to/key", api_key)
+                            .secure(true)
+                            .on_input(SettingsMessage::ApiKeyChanged),
+                    )
+                    .push_maybe(settings_state.live_settings.api_key_hint().map(|hint| {
+                        text(hint).size(12).style(|theme: &Theme| text::Style {
+                            color: Some(theme.palette().danger),
+                        })
+                    }))
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Model",
+                        text_input("Model ID e.g. gpt-4o-mini", model)
+                            .on_input(SettingsMessage::ModelChanged),
+                    )
+                    .spacing(4.99) // weird clipping shit with text input
+                    .into(),
+                    pair_in_column(
+                        "User",
+                        text_input("End user ID, blank to not send one", user)
+                            .on_input(SettingsMessage::UserChanged),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Default System Prompt",
+                        text_input(
+                            "Starts new conversations, blank for none",
+                            default_system_prompt,
+                        )
+                        .on_input(SettingsMessage::DefaultSystemPromptChanged),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Conversation Template",
+                        row([
+                            text(match conversation_template.len() {
+                                0 => "None, saved from a conversation".to_string(),
+                                1 => "1 message".to_string(),
+                                len => format!("{len} messages"),
+                            })
+                            .width(Length::Fill)
+                            .into(),
+                            button("Clear")
+                                .style(button::secondary)
+                                .on_press_maybe((!conversation_template.is_empty()).then_some(
+                                    SettingsMessage::ConversationTemplateChanged(vec![]),
+                                ))
+                                .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Max Tokens",
+                        parsable_text_input(
+                            "e.g. 1000",
+                            max_tokens,
+                            SettingsMessage::MaxTokensChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Max Tokens Field",
+                        pick_list(
+                            MaxTokensField::ALL,
+                            Some(*max_tokens_field),
+                            SettingsMessage::MaxTokensFieldChanged,
+                        )
+                        .width(Length::Fill),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Temperature",
+                        parsable_text_input(
+                            "e.g. 1.0",
+                            temperature,
+                            SettingsMessage::TemperatureChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Seed",
+                        validated_text_input(
+                            "Blank to not send one",
+                            seed,
+                            valid_seed(seed),
+                            SettingsMessage::SeedChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Stop Sequences",
+                        row([
+                            text_input("e.g. \\n\\n, ###", stop_sequences)
+                                .on_input(SettingsMessage::StopSequencesChanged)
+                                .into(),
+                            pick_list(
+                                StopFormat::ALL,
+                                Some(*stop_format),
+                                SettingsMessage::StopFormatChanged,
+                            )
+                            .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Theme",
+                        pick_list(ThemeMode::ALL, Some(*theme), SettingsMessage::ThemeChanged)
+                            .width(Length::Fill),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        row([
+                            container(text(format!("UI Scale ({}%)", ui_scale)))
+                                .padding(Padding {
+                                    top: 5.0,
+                                    bottom: 5.0,
+                                    right: 0.0,
+                                    left: 0.0,
                                 })
                                 .into(),
                             button("Reset")
@@ -345,7 +1761,516 @@ impl SettingsView {
                     )
                     .spacing(5)
                     .into(),
-                    match settings_state.valid_parsables() {
+                    pair_in_column(
+                        row([
+                            container(text(format!("Editor Font Size ({})", font_size)))
+                                .padding(Padding {
+                                    top: 5.0,
+                                    bottom: 5.0,
+                                    right: 0.0,
+                                    left: 0.0,
+                                })
+                                .into(),
+                            button("Reset")
+                                .style(button::secondary)
+                                .on_press(SettingsMessage::FontSizeChanged(default_font_size()))
+                                .into(),
+                        ])
+                        .spacing(7),
+                        slider(10.0..=32.0, *font_size, SettingsMessage::FontSizeChanged)
+                            .step(1.0),
+                    )
+                    .spacing(5)
+                    .into(),
+                    checkbox("Show roles as coloured badges", *role_badges)
+                        .on_toggle(SettingsMessage::RoleBadgesChanged)
+                        .into(),
+                    checkbox(
+                        "Stick to bottom automatically when scrolling",
+                        *auto_stick_to_bottom,
+                    )
+                    .on_toggle(SettingsMessage::AutoStickToBottomChanged)
+                    .into(),
+                    pair_in_column(
+                        "Tokenizer Model",
+                        text_input(
+                            "Blank to use the model being run, e.g. gpt-4o",
+                            tokenizer_model,
+                        )
+                        .on_input(SettingsMessage::TokenizerModelChanged),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Token Budget",
+                        parsable_text_input(
+                            "e.g. 100000",
+                            token_budget,
+                            SettingsMessage::TokenBudgetChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Delta Batching (ms)",
+                        parsable_text_input(
+                            "0 to paste each delta as it arrives",
+                            delta_batch_interval,
+                            SettingsMessage::DeltaBatchIntervalChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Typewriter (chars/s)",
+                        parsable_text_input(
+                            "0 to paste deltas as they arrive",
+                            typewriter_rate,
+                            SettingsMessage::TypewriterRateChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Request Queue",
+                        row([
+                            parsable_text_input(
+                                "Min ms between requests, 0 for none",
+                                request_interval,
+                                SettingsMessage::RequestIntervalChanged,
+                            )
+                            .into(),
+                            parsable_text_input(
+                                "Max at once, 0 for no limit",
+                                max_concurrent_requests,
+                                SettingsMessage::MaxConcurrentRequestsChanged,
+                            )
+                            .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Connection Retries",
+                        parsable_text_input(
+                            "Of failing to connect, 0 for none",
+                            connect_retries,
+                            SettingsMessage::ConnectRetriesChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    checkbox(
+                        "Trim the response's leading whitespace",
+                        *trim_leading_whitespace,
+                    )
+                    .on_toggle(SettingsMessage::TrimLeadingWhitespaceChanged)
+                    .into(),
+                    checkbox(
+                        "Trim the trailing whitespace of sent messages",
+                        *trim_trailing_whitespace,
+                    )
+                    .on_toggle(SettingsMessage::TrimTrailingWhitespaceChanged)
+                    .into(),
+                    checkbox(
+                        "Fill in {{now}}, {{date}} and {{env:VAR}} when sending",
+                        *fill_placeholders,
+                    )
+                    .on_toggle(SettingsMessage::FillPlaceholdersChanged)
+                    .into(),
+                    pair_in_column(
+                        "Post-processing",
+                        Column::with_children(PostProcessor::ALL.iter().map(|&processor| {
+                            checkbox(
+                                processor.to_string(),
+                                post_processors.contains(&processor),
+                            )
+                            .on_toggle(move |enabled| {
+                                SettingsMessage::PostProcessorToggled(processor, enabled)
+                            })
+                            .into()
+                        }))
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Context Window (messages)",
+                        parsable_text_input(
+                            "0 to send every message",
+                            context_window_messages,
+                            SettingsMessage::ContextWindowMessagesChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Stop After (tokens)",
+                        parsable_text_input(
+                            "0 to never stop early, even if max tokens is ignored",
+                            stop_after_tokens,
+                            SettingsMessage::StopAfterTokensChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Streaming Line Cap",
+                        parsable_text_input(
+                            "0 to always show the whole response while streaming",
+                            stream_line_cap,
+                            SettingsMessage::StreamLineCapChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Scrollback (messages)",
+                        column([
+                            parsable_text_input(
+                                "0 to keep every message in view",
+                                max_messages,
+                                SettingsMessage::MaxMessagesChanged,
+                            )
+                            .into(),
+                            checkbox(
+                                "Save the hidden messages with the conversation",
+                                *save_hidden_messages,
+                            )
+                            .on_toggle(SettingsMessage::SaveHiddenMessagesChanged)
+                            .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    checkbox(
+                        "Add an assistant message to respond in when running",
+                        *auto_add_assistant_message,
+                    )
+                    .on_toggle(SettingsMessage::AutoAddAssistantMessageChanged)
+                    .into(),
+                    checkbox(
+                        "Compatibility: don't send a trailing assistant message (no prefill)",
+                        *drop_trailing_assistant,
+                    )
+                    .on_toggle(SettingsMessage::DropTrailingAssistantChanged)
+                    .into(),
+                    checkbox(
+                        "Compatibility: send an empty assistant message after a trailing user \
+                         message",
+                        *empty_assistant_turn,
+                    )
+                    .on_toggle(SettingsMessage::EmptyAssistantTurnChanged)
+                    .into(),
+                    pair_in_column(
+                        checkbox("Request logprobs", *logprobs)
+                            .on_toggle(SettingsMessage::LogprobsChanged),
+                        parsable_text_input(
+                            "Top logprobs per token e.g. 5",
+                            top_logprobs,
+                            SettingsMessage::TopLogprobsChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Structured Output Schema",
+                        column([
+                            validated_text_input(
+                                "JSON schema, blank for free form responses",
+                                json_schema,
+                                schema::parse(json_schema).is_ok(),
+                                SettingsMessage::JsonSchemaChanged,
+                            )
+                            .into(),
+                            checkbox("Flag responses that don't match", *validate_json_schema)
+                                .on_toggle(SettingsMessage::ValidateJsonSchemaChanged)
+                                .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "HTTP Proxy",
+                        validated_text_input(
+                            "Blank to use the environment",
+                            http_proxy,
+                            openai::valid_proxy(http_proxy),
+                            SettingsMessage::HttpProxyChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "HTTPS Proxy",
+                        validated_text_input(
+                            "Blank to use the environment",
+                            https_proxy,
+                            openai::valid_proxy(https_proxy),
+                            SettingsMessage::HttpsProxyChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        checkbox(
+                            "Accept invalid TLS certificates",
+                            *danger_accept_invalid_certs,
+                        )
+                        .on_toggle(SettingsMessage::DangerAcceptInvalidCertsChanged),
+                        text("Insecure, only use this for servers you trust e.g. self-signed local ones")
+                            .size(12)
+                            .style(move |theme: &Theme| text::Style {
+                                color: Some(match *danger_accept_invalid_certs {
+                                    true => theme.palette().danger,
+                                    false => theme.extended_palette().background.weak.text,
+                                }),
+                            }),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "CA Certificate",
+                        text_input("Path to a PEM file, blank for none", ca_certificate_path)
+                            .on_input(SettingsMessage::CaCertificatePathChanged),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "User Agent",
+                        validated_text_input(
+                            "Blank to not send one",
+                            user_agent,
+                            openai::valid_user_agent(user_agent.trim()),
+                            SettingsMessage::UserAgentChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Raw Log",
+                        column([
+                            text_input("Path to log requests to, blank to not log", raw_log_path)
+                                .on_input(SettingsMessage::RawLogPathChanged)
+                                .into(),
+                            text("Has your prompts, responses and anything else sent, don't share it")
+                                .size(12)
+                                .style(|theme: &Theme| text::Style {
+                                    color: Some(theme.extended_palette().background.weak.text),
+                                })
+                                .into(),
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Delta Pointer",
+                        validated_text_input(
+                            openai::DEFAULT_DELTA_POINTER,
+                            delta_pointer,
+                            openai::valid_json_pointer(delta_pointer),
+                            SettingsMessage::DeltaPointerChanged,
+                        ),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Path Template",
+                        text_input(
+                            "e.g. v1/models/{model}/chat/completions, blank for the default",
+                            path_template,
+                        )
+                        .on_input(SettingsMessage::PathTemplateChanged),
+                    )
+                    .push_maybe(path_template.contains("{model}").then(|| {
+                        checkbox("Also send the model in the body", *model_in_body)
+                            .on_toggle(SettingsMessage::ModelInBodyChanged)
+                    }))
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Presets",
+                        column(
+                            presets
+                                .iter()
+                                .enumerate()
+                                .map(|(index, preset)| {
+                                    row([
+                                        text(preset.name.as_str()).into(),
+                                        horizontal_space().into(),
+                                        button(text("Delete").size(12))
+                                            .style(button::danger)
+                                            .on_press(SettingsMessage::DeletePreset(index))
+                                            .into(),
+                                    ])
+                                    .into()
+                                })
+                                .chain([
+                                    text_input("Preset name", &settings_state.new_preset_name)
+                                        .on_input(SettingsMessage::PresetNameChanged)
+                                        .into(),
+                                    text_input(
+                                        "System prompt (optional)",
+                                        &settings_state.new_preset_system_prompt,
+                                    )
+                                    .on_input(SettingsMessage::PresetSystemPromptChanged)
+                                    .into(),
+                                    button(
+                                        container("Add preset from the current params")
+                                            .center_x(Length::Fill),
+                                    )
+                                    .style(button::secondary)
+                                    .on_press_maybe(
+                                        settings_state
+                                            .new_preset()
+                                            .map(|_| SettingsMessage::AddPreset),
+                                    )
+                                    .into(),
+                                ]),
+                        )
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Model Aliases",
+                        column(
+                            model_aliases
+                                .iter()
+                                .enumerate()
+                                .map(|(index, alias)| {
+                                    row([
+                                        text(format!("{} → {}", alias.alias, alias.model))
+                                            .into(),
+                                        horizontal_space().into(),
+                                        button(text("Delete").size(12))
+                                            .style(button::danger)
+                                            .on_press(SettingsMessage::DeleteModelAlias(index))
+                                            .into(),
+                                    ])
+                                    .into()
+                                })
+                                .chain([
+                                    row([
+                                        text_input("Alias", &settings_state.new_alias.alias)
+                                            .on_input(SettingsMessage::NewAliasChanged)
+                                            .into(),
+                                        text_input("Full model id", &settings_state.new_alias.model)
+                                            .on_input(SettingsMessage::NewAliasModelChanged)
+                                            .into(),
+                                    ])
+                                    .spacing(5)
+                                    .into(),
+                                    button(container("Add alias").center_x(Length::Fill))
+                                        .style(button::secondary)
+                                        .on_press_maybe(
+                                            settings_state
+                                                .new_alias()
+                                                .map(|_| SettingsMessage::AddModelAlias),
+                                        )
+                                        .into(),
+                                ]),
+                        )
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    pair_in_column(
+                        "Import / Export",
+                        column([
+                            text_input("Path to a settings file", &settings_state.transfer_path)
+                                .on_input(SettingsMessage::TransferPathChanged)
+                                .into(),
+                            row([
+                                button(container("Import").center_x(Length::Fill))
+                                    .style(button::secondary)
+                                    .on_press_maybe(
+                                        (!settings_state.transfer_path.is_empty())
+                                            .then_some(SettingsMessage::ImportSettings),
+                                    )
+                                    .into(),
+                                button(container("Export saved").center_x(Length::Fill))
+                                    .style(button::secondary)
+                                    .on_press_maybe(
+                                        (!settings_state.transfer_path.is_empty())
+                                            .then_some(SettingsMessage::ExportSettings),
+                                    )
+                                    .into(),
+                            ])
+                            .spacing(5)
+                            .into(),
+                            match &settings_state.transfer_status {
+                                Some(status) => {
+                                    let is_err = status.is_err();
+
+                                    text(match status {
+                                        Ok(status) | Err(status) => status.as_str(),
+                                    })
+                                    .size(12)
+                                    .style(move |theme: &Theme| text::Style {
+                                        color: is_err.then(|| theme.palette().danger),
+                                    })
+                                    .into()
+                                }
+                                None => Column::new().into(),
+                            },
+                        ])
+                        .spacing(5),
+                    )
+                    .spacing(5)
+                    .into(),
+                    match settings_state.client.error() {
+                        Some(err) => text(format!("Couldn't build the HTTP client: {err}"))
+                            .size(12)
+                            .style(|theme: &Theme| text::Style {
+                                color: Some(theme.palette().danger),
+                            })
+                            .into(),
+                        None => Column::new().into(),
+                    },
+                    match settings_state.unsaved_changes() {
+                        true => column([
+                            button(
+                                text(match settings_state.show_changes {
+                                    true => "Hide changes".to_string(),
+                                    false => format!(
+                                        "View changes ({})",
+                                        settings_state.changes().len()
+                                    ),
+                                })
+                                .size(12),
+                            )
+                            .style(button::secondary)
+                            .on_press(SettingsMessage::ToggleChanges)
+                            .into(),
+                            match settings_state.show_changes {
+                                true => column(settings_state.changes().into_iter().map(
+                                    |change| {
+                                        text(format!(
+                                            "{}: {} → {}",
+                                            change.field, change.saved, change.live
+                                        ))
+                                        .size(12)
+                                        .into()
+                                    },
+                                ))
+                                .spacing(2)
+                                .into(),
+                                false => Column::new().into(),
+                            },
+                        ])
+                        .spacing(5)
+                        .into(),
+                        false => Column::new().into(),
+                    },
+                    checkbox("Save changes automatically", *autosave)
+                        .on_toggle(SettingsMessage::AutosaveChanged)
+                        .into(),
+                    match settings_state.valid_inputs() {
                         true => button(container("Save").center_x(Length::Fill))
                             .on_press_maybe(match settings_state.unsaved_changes() {
                                 true => Some(SettingsMessage::Save),
@@ -357,8 +2282,9 @@ impl SettingsView {
                             .into(),
                     },
                 ])
-                .spacing(10)
-                .into()
+                .spacing(10);
+
+                scrollable(settings_column).spacing(3).into()
             }
         })
         .style(|theme| container::rounded_box(theme).border(Border::default()))
@@ -370,8 +2296,14 @@ impl SettingsView {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
     use crate::PlaygroundMessage;
-    use crate::settings::{SerializedSettings, SettingsMessage, SettingsState};
+    use crate::openai::{BuiltClient, Endpoint, MaxTokensField, Provider};
+    use crate::settings::{
+        parse_stop_sequences, GenerationParams, Parsable, QuickSetup, SerializedSettings,
+        SettingChange, SettingsMessage, SettingsState, SettingsView, AUTOSAVE_DELAY,
+    };
 
     #[test]
     fn size() {
@@ -380,4 +2312,287 @@ mod tests {
         dbg!(size_of::<SettingsMessage>());
         dbg!(size_of::<PlaygroundMessage>());
     }
+
+    #[test]
+    fn apply_generation_params() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+        let _ = view.update(SettingsMessage::ApplyGenerationParams(Box::new(
+            GenerationParams {
+                model: "m".to_string(),
+                max_tokens: 64,
+                max_tokens_field: MaxTokensField::MaxCompletionTokens,
+                temperature: 0.5,
+                endpoint: Endpoint::Completions,
+            },
+        )));
+
+        let state = view.settings();
+        assert_eq!(state.live_settings.model, "m");
+        assert_eq!(state.live_settings.max_tokens.parsed(), Some(64));
+        assert_eq!(state.live_settings.temperature.parsed(), Some(0.5));
+        // Only applied, it still has to be saved
+        assert_eq!(state.saved().model, "");
+    }
+
+    #[test]
+    fn model_aliases() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+        let _ = view.update(SettingsMessage::NewAliasChanged("llama70b".to_string()));
+        let _ = view.update(SettingsMessage::NewAliasModelChanged(
+            "meta/llama-70b".to_string(),
+        ));
+        let _ = view.update(SettingsMessage::AddModelAlias);
+
+        let state = view.settings();
+        let settings = &state.live_settings;
+        assert_eq!(
+            settings.expand_model_alias("llama70b".to_string()),
+            "meta/llama-70b"
+        );
+        assert_eq!(settings.expand_model_alias("gpt-4o".to_string()), "gpt-4o");
+    }
+
+    #[test]
+    fn changes() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+        assert!(view.settings().changes().is_empty());
+
+        let _ = view.update(SettingsMessage::ModelChanged("gpt".to_string()));
+        let _ = view.update(SettingsMessage::ApiKeyChanged("secret".to_string()));
+        let _ = view.update(SettingsMessage::MaxTokensChanged(Parsable::parse(
+            "25x".to_string(),
+        )));
+
+        let change = |field: &str, saved: &str, live: &str| SettingChange {
+            field: field.to_string(),
+            saved: saved.to_string(),
+            live: live.to_string(),
+        };
+
+        assert_eq!(
+            view.settings().changes(),
+            [
+                change("api_key", "(hidden)", "(hidden)"),
+                change("max_tokens", "1000", "25x"),
+                change("model", "(blank)", "gpt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_visibility_keeps_unsaved_changes() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+        let _ = view.update(SettingsMessage::ModelChanged("unsaved".to_string()));
+        let _ = view.update(SettingsMessage::SettingsVisibleChanged(false));
+
+        let state = view.settings();
+        assert!(!state.saved().settings_visible);
+        assert!(!state.live_settings.settings_visible);
+        assert_eq!(state.saved().model, "");
+        assert_eq!(state.live_settings.model, "unsaved");
+    }
+
+    #[test]
+    fn provider_label() {
+        let mut settings = SerializedSettings {
+            base_url: "https://api.openai.com/".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(settings.provider_label(), "api.openai.com");
+
+        settings.base_url = "localhost".to_string();
+        assert_eq!(settings.provider_label(), "No base URL");
+
+        settings.provider = Provider::Mock;
+        assert_eq!(settings.provider_label(), "Mock");
+    }
+
+    #[test]
+    fn quick_setup() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::new(SerializedSettings {
+            api_key: "key".to_string(),
+            endpoint: Endpoint::Completions,
+            ..Default::default()
+        })));
+
+        let _ = view.update(SettingsMessage::QuickSetup(QuickSetup::Groq));
+
+        let state = view.settings();
+        assert_eq!(state.live_settings.base_url, "https://api.groq.com/openai/");
+        assert_eq!(state.live_settings.endpoint, Endpoint::ChatCompletions);
+        assert_eq!(state.live_settings.api_key, "key");
+        // Only the live settings, to review before saving
+        assert_eq!(state.saved().endpoint, Endpoint::Completions);
+    }
+
+    #[test]
+    fn api_key_hint() {
+        let settings = |base_url: &str, api_key: &str| SerializedSettings {
+            provider: Provider::OpenAiCompatible,
+            base_url: base_url.to_string(),
+            api_key: api_key.to_string(),
+            ..Default::default()
+        };
+
+        assert!(settings("https://api.openai.com/", "sk-proj-abc")
+            .api_key_hint()
+            .is_none());
+        assert!(settings("https://api.openai.com", "gsk_abc")
+            .api_key_hint()
+            .is_some_and(|hint| hint.contains("sk-")));
+        assert!(settings("https://api.groq.com/openai/", "sk-abc")
+            .api_key_hint()
+            .is_some());
+
+        // Lenient where there's no telling
+        assert!(settings("https://api.openai.com/", "")
+            .api_key_hint()
+            .is_none());
+        assert!(settings("https://api.together.xyz/", "abc")
+            .api_key_hint()
+            .is_none());
+        assert!(settings("https://gateway.example.com/", "abc")
+            .api_key_hint()
+            .is_none());
+        assert!(settings("https://api.openai.com/", "file:/run/secrets/key")
+            .api_key_hint()
+            .is_none());
+    }
+
+    #[test]
+    fn stop_sequences() {
+        assert_eq!(
+            parse_stop_sequences(r" \n\n , ###,, a\, b ,\\end\q,"),
+            ["\n\n", "###", "a, b", "\\end\\q"]
+        );
+        assert!(parse_stop_sequences("  ").is_empty());
+    }
+
+    #[test]
+    fn autosave() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::new(SerializedSettings {
+            autosave: true,
+            ..Default::default()
+        })));
+
+        // As if the last edit was a while ago
+        let settle = |view: &mut SettingsView| {
+            if let SettingsView::Loaded(state) = view {
+                state.last_edited = Some(Instant::now() - AUTOSAVE_DELAY);
+            }
+        };
+
+        let _ = view.update(SettingsMessage::ModelChanged("gpt".to_string()));
+        assert!(!view.settings().autosave_due());
+
+        settle(&mut view);
+        assert!(view.settings().autosave_due());
+
+        // Not while there's something invalid to save
+        let _ = view.update(SettingsMessage::MaxTokensChanged(Parsable::parse(
+            "25x".to_string(),
+        )));
+        settle(&mut view);
+        assert!(!view.settings().autosave_due());
+
+        // Stops waiting until the next edit
+        let _ = view.update(SettingsMessage::AutosaveTick);
+        assert!(view.settings().last_edited.is_none());
+
+        let _ = view.update(SettingsMessage::MaxTokensChanged(Parsable::parse(
+            "25".to_string(),
+        )));
+        assert!(view.settings().last_edited.is_some());
+        settle(&mut view);
+        let _ = view.update(SettingsMessage::AutosaveTick);
+
+        // Saving, the next edit starts the wait over
+        assert!(view.settings().last_edited.is_none());
+
+        // Edits while it's being written aren't lost when it's done
+        let sent = view.settings().live_settings.clone();
+        let _ = view.update(SettingsMessage::ModelChanged("gpt-4o".to_string()));
+        let _ = view.update(SettingsMessage::SaveResult(Ok(Box::new(sent))));
+
+        let state = view.settings();
+        assert_eq!(state.saved().model, "gpt");
+        assert_eq!(state.live_settings.model, "gpt-4o");
+        assert!(state.unsaved_changes());
+        assert!(state.last_edited.is_some());
+    }
+
+    #[test]
+    fn import_only_replaces_live_settings() {
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+
+        let imported = SerializedSettings {
+            model: "imported".to_string(),
+            ..Default::default()
+        };
+
+        let _ = view.update(SettingsMessage::ImportResult(Ok(Box::new(
+            imported.clone(),
+        ))));
+
+        let state = view.settings();
+        assert_eq!(state.live_settings, imported);
+        assert_eq!(state.saved().model, "");
+        assert!(state.unsaved_changes());
+
+        // Missing fields fall back to their defaults
+        let partial: SerializedSettings = serde_json::from_str(
+            r#"{"base_url": "", "api_key": "", "model": "m",
+                "max_tokens": {"content": "1", "parsed": 1},
+                "temperature": {"content": "1", "parsed": 1.0}}"#,
+        )
+        .unwrap();
+        assert_eq!(partial.model, "m");
+        assert!(partial.auto_stick_to_bottom);
+    }
+
+    #[tokio::test]
+    async fn client_rebuilt_after_save() {
+        let built = |settings: &SerializedSettings| {
+            let options = settings.client_options();
+
+            async move { SettingsMessage::ClientBuilt(Box::new(BuiltClient::build(options).await)) }
+        };
+
+        let mut view = SettingsView::Loading;
+        let _ = view.update(SettingsMessage::Load(Box::default()));
+
+        let saved = view.settings().saved().clone();
+        let mut new_settings = saved.clone();
+        new_settings.danger_accept_invalid_certs = true;
+
+        // Built in the background once loaded
+        assert!(view.settings().client().outdated(&saved.client_options()));
+        let _ = view.update(built(&saved).await);
+        assert!(!view.settings().client().outdated(&saved.client_options()));
+        assert!(view.settings().client().get().is_ok());
+
+        let _ = view.update(SettingsMessage::SaveResult(Ok(Box::new(
+            new_settings.clone(),
+        ))));
+
+        // One built for the settings before the save is dropped
+        let _ = view.update(built(&saved).await);
+        assert!(view
+            .settings()
+            .client()
+            .outdated(&new_settings.client_options()));
+
+        let _ = view.update(built(&new_settings).await);
+        assert!(!view
+            .settings()
+            .client()
+            .outdated(&new_settings.client_options()));
+    }
 }
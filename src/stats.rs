@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::placeholders;
+
+/// A finished run that received at least one token
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub model: String,
+    pub time_to_first_token: Duration,
+    /// From sending the request to the end of the stream
+    pub duration: Duration,
+    pub tokens: usize,
+    /// Estimated from the messages sent
+    pub prompt_tokens: usize,
+    pub finished_at: SystemTime,
+}
+
+impl Sample {
+    fn tokens_per_second(&self) -> Option<f64> {
+        let generating = self.duration.saturating_sub(self.time_to_first_token);
+
+        (!generating.is_zero()).then(|| self.tokens as f64 / generating.as_secs_f64())
+    }
+}
+
+/// Aggregates of the samples of a single model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSummary {
+    pub model: String,
+    pub requests: usize,
+    pub avg_time_to_first_token: Duration,
+    /// `None` if no sample generated for a measurable amount of time
+    pub avg_tokens_per_second: Option<f64>,
+    pub total_tokens: usize,
+}
+
+/// Samples of the runs this session, the oldest are dropped past `MAX_SAMPLES`
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    samples: VecDeque<Sample>,
+}
+
+impl SessionStats {
+    const MAX_SAMPLES: usize = 500;
+
+    pub fn record(&mut self, sample: Sample) {
+        if self.samples.len() == Self::MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// One row per sample, oldest first, for comparing runs in a spreadsheet
+    pub fn to_csv(&self) -> String {
+        let mut csv = "timestamp,model,prompt_tokens,completion_tokens,time_to_first_token_s,\
+                       tokens_per_second\n"
+            .to_string();
+
+        for sample in &self.samples {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.3},{}\n",
+                placeholders::timestamp(sample.finished_at),
+                csv_field(&sample.model),
+                sample.prompt_tokens,
+                sample.tokens,
+                sample.time_to_first_token.as_secs_f64(),
+                sample
+                    .tokens_per_second()
+                    .map(|rate| format!("{rate:.2}"))
+                    .unwrap_or_default()
+            ));
+        }
+
+        csv
+    }
+
+    /// One summary per model, in the order each model was first used
+    pub fn summaries(&self) -> Vec<ModelSummary> {
+        let mut models: Vec<&str> = vec![];
+
+        for sample in &self.samples {
+            if !models.contains(&sample.model.as_str()) {
+                models.push(&sample.model);
+            }
+        }
+
+        models
+            .into_iter()
+            .map(|model| {
+                let samples = self
+                    .samples
+                    .iter()
+                    .filter(|sample| sample.model == model)
+                    .collect::<Vec<_>>();
+
+                let rates = samples
+                    .iter()
+                    .filter_map(|sample| sample.tokens_per_second())
+                    .collect::<Vec<_>>();
+
+                ModelSummary {
+                    model: model.to_string(),
+                    requests: samples.len(),
+                    avg_time_to_first_token: samples
+                        .iter()
+                        .map(|sample| sample.time_to_first_token)
+                        .sum::<Duration>()
+                        / samples.len() as u32,
+                    avg_tokens_per_second: (!rates.is_empty())
+                        .then(|| rates.iter().sum::<f64>() / rates.len() as f64),
+                    total_tokens: samples.iter().map(|sample| sample.tokens).sum(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Quoted if it would otherwise split the row, with quotes doubled
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::stats::{Sample, SessionStats};
+
+    fn sample(model: &str, ttft_ms: u64, duration_ms: u64, tokens: usize) -> Sample {
+        Sample {
+            model: model.to_string(),
+            time_to_first_token: Duration::from_millis(ttft_ms),
+            duration: Duration::from_millis(duration_ms),
+            tokens,
+            prompt_tokens: 20,
+            finished_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn summaries() {
+        let mut stats = SessionStats::default();
+        stats.record(sample("a", 100, 1100, 10));
+        stats.record(sample("b", 500, 500, 3));
+        stats.record(sample("a", 300, 2300, 40));
+
+        let summaries = stats.summaries();
+        assert_eq!(summaries.len(), 2);
+
+        let a = &summaries[0];
+        assert_eq!(a.model, "a");
+        assert_eq!(a.requests, 2);
+        assert_eq!(a.avg_time_to_first_token, Duration::from_millis(200));
+        assert_eq!(a.avg_tokens_per_second, Some(15.0));
+        assert_eq!(a.total_tokens, 50);
+
+        // Everything arrived with the first token
+        let b = &summaries[1];
+        assert_eq!(b.requests, 1);
+        assert_eq!(b.avg_tokens_per_second, None);
+    }
+
+    #[test]
+    fn csv() {
+        let mut stats = SessionStats::default();
+        stats.record(sample("a", 100, 1100, 10));
+        stats.record(sample("org/model, \"v2\"", 500, 500, 3));
+
+        assert_eq!(
+            stats.to_csv(),
+            "timestamp,model,prompt_tokens,completion_tokens,time_to_first_token_s,tokens_per_second\n\
+             2023-11-14T22:13:20Z,a,20,10,0.100,10.00\n\
+             2023-11-14T22:13:20Z,\"org/model, \"\"v2\"\"\",20,3,0.500,\n"
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let mut stats = SessionStats::default();
+
+        for tokens in 0..SessionStats::MAX_SAMPLES + 10 {
+            stats.record(sample("a", 0, 0, tokens));
+        }
+
+        assert_eq!(stats.len(), SessionStats::MAX_SAMPLES);
+        assert_eq!(stats.samples.front().unwrap().tokens, 10);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+/// Rough token count, ~4 characters per token for english text
+pub fn estimate(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// A token count, shown with a `~` if it's only estimated
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Count {
+    pub tokens: usize,
+    pub exact: bool,
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.exact {
+            true => write!(f, "{}", self.tokens),
+            false => write!(f, "~{}", self.tokens),
+        }
+    }
+}
+
+impl Sum for Count {
+    /// Exact only if every count is
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(
+            Count {
+                tokens: 0,
+                exact: true,
+            },
+            |sum, count| Count {
+                tokens: sum.tokens + count.tokens,
+                exact: sum.exact && count.exact,
+            },
+        )
+    }
+}
+
+/// Counts with the BPE tokenizer of a model, or estimates if there isn't one for it
+#[derive(Clone)]
+pub struct Counter {
+    /// Only set along with its BPE
+    tokenizer: Option<Tokenizer>,
+    bpe: Option<Arc<CoreBPE>>,
+}
+
+impl Debug for Counter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Counter")
+            .field("tokenizer", &self.tokenizer)
+            .finish()
+    }
+}
+
+/// A count along with what it was counted from, so it's only redone once either changes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Counted {
+    text_hash: u64,
+    tokenizer: Option<Tokenizer>,
+    pub count: Count,
+}
+
+impl Counter {
+    /// The tokenizers are cached by encoding since building one parses its whole vocabulary,
+    /// and most models share one of a few
+    pub fn for_model(model: &str) -> Self {
+        static TOKENIZERS: OnceLock<Mutex<HashMap<Tokenizer, Option<Arc<CoreBPE>>>>> =
+            OnceLock::new();
+
+        let Some(tokenizer) = get_tokenizer(model.trim()) else {
+            return Self {
+                tokenizer: None,
+                bpe: None,
+            };
+        };
+
+        let mut tokenizers = TOKENIZERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let bpe = tokenizers
+            .entry(tokenizer)
+            .or_insert_with(|| {
+                tiktoken_rs::get_bpe_from_tokenizer(tokenizer)
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone();
+
+        Self {
+            tokenizer: bpe.is_some().then_some(tokenizer),
+            bpe,
+        }
+    }
+
+    /// `counted` if it's of the same text with the same tokenizer, otherwise counted again
+    pub fn recount(&self, text: &str, counted: Option<Counted>) -> Counted {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let text_hash = hasher.finish();
+
+        match counted {
+            Some(counted)
+                if counted.text_hash == text_hash && counted.tokenizer == self.tokenizer =>
+            {
+                counted
+            }
+            _ => Counted {
+                text_hash,
+                tokenizer: self.tokenizer,
+                count: self.count(text),
+            },
+        }
+    }
+
+    /// The count in `counted` if it was counted with this tokenizer
+    pub fn counted(&self, counted: Option<Counted>) -> Option<Count> {
+        counted
+            .filter(|counted| counted.tokenizer == self.tokenizer)
+            .map(|counted| counted.count)
+    }
+
+    pub fn count(&self, text: &str) -> Count {
+        match &self.bpe {
+            Some(bpe) => Count {
+                tokens: bpe.encode_with_special_tokens(text).len(),
+                exact: true,
+            },
+            None => Count {
+                tokens: estimate(text),
+                exact: false,
+            },
+        }
+    }
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Minutes to read the words at an average ~240 words per minute, rounded up
+pub fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(240)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    #[test]
+    fn estimate() {
+        assert_eq!(super::estimate(""), 0);
+        assert_eq!(super::estimate("hi"), 1);
+        assert_eq!(super::estimate("four"), 1);
+        assert_eq!(super::estimate("hello world"), 3);
+    }
+
+    #[test]
+    fn counter() {
+        let estimated = super::Counter::for_model("");
+        assert_eq!(estimated.count("hello world").to_string(), "~3");
+
+        // Unknown models fall back to the estimate
+        let unknown = super::Counter::for_model("meta-llama/Meta-Llama-3.1-70B-Instruct-Turbo");
+        assert!(!unknown.count("hello world").exact);
+
+        let exact = super::Counter::for_model("gpt-4o");
+        assert_eq!(exact.count("hello world").to_string(), "2");
+
+        let sum = [exact.count("hello world"), estimated.count("hi")]
+            .into_iter()
+            .sum::<super::Count>();
+        assert_eq!(sum.to_string(), "~3");
+
+        // Models sharing an encoding share its tokenizer
+        let mini = super::Counter::for_model("gpt-4o-mini");
+        assert!(Arc::ptr_eq(
+            mini.bpe.as_ref().unwrap(),
+            exact.bpe.as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn recount() {
+        let exact = super::Counter::for_model("gpt-4o");
+        let estimated = super::Counter::for_model("");
+
+        let counted = exact.recount("hello world", None);
+        assert_eq!(counted.count.tokens, 2);
+        assert_eq!(exact.recount("hello world", Some(counted)), counted);
+        assert_eq!(exact.recount("hello", Some(counted)).count.tokens, 1);
+
+        // Counted again once the tokenizer changes
+        assert_eq!(estimated.counted(Some(counted)), None);
+        assert!(!estimated.recount("hello world", Some(counted)).count.exact);
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(super::word_count(""), 0);
+        assert_eq!(super::word_count("  one\ntwo  three "), 3);
+
+        assert_eq!(super::reading_minutes(0), 0);
+        assert_eq!(super::reading_minutes(1), 1);
+        assert_eq!(super::reading_minutes(240), 1);
+        assert_eq!(super::reading_minutes(241), 2);
+    }
+}